@@ -1,11 +1,15 @@
 //! Bit vector implementation.
 
-use std::{fmt::Debug, ops::Index};
+use std::{fmt::Debug, iter, ops::Index};
+
+use mirabel::error::{Error, ErrorCode::InvalidInput, Result};
 
 const BITS: usize = usize::BITS as usize;
 
 /// Simple implementation of a bit vector on a [`Vec<usize>`].
-#[derive(Clone, PartialEq, Eq)]
+///
+/// [`Hash`] is consistent with [`Eq`] because unused bits are always zero.
+#[derive(Clone, PartialEq, Eq, Hash)]
 pub(crate) struct BitVec {
     /// Unused bits are always zero.
     data: Vec<usize>,
@@ -21,10 +25,18 @@ impl BitVec {
         }
     }
 
+    /// Create an empty bit vector with room for at least `bits` bits.
+    #[allow(dead_code)]
+    pub(crate) fn with_capacity(bits: usize) -> Self {
+        BitVec {
+            data: Vec::with_capacity(div_ceil(bits, BITS)),
+            length: 0,
+        }
+    }
+
     pub(crate) fn set(&mut self, index: usize, value: bool) {
-        assert!(index < self.length, "index out of range");
-        let mask = 1 << (index % BITS);
-        let container = &mut self.data[index / BITS];
+        let (container, mask) = self.word_and_mask(index);
+        let container = &mut self.data[container];
         if value {
             *container |= mask;
         } else {
@@ -32,6 +44,129 @@ impl BitVec {
         }
     }
 
+    /// Set all bits in `start..end` to `value`.
+    ///
+    /// Whole containers are set at once.
+    ///
+    /// # Panics
+    /// Panics if `end` is out of range or `start` is larger than `end`.
+    #[allow(dead_code)]
+    pub(crate) fn set_range(&mut self, start: usize, end: usize, value: bool) {
+        assert!(start <= end && end <= self.length, "range out of bounds");
+        let mut index = start;
+        while index < end {
+            let offset = index % BITS;
+            let count = (BITS - offset).min(end - index);
+            let mask = if count == BITS {
+                usize::MAX
+            } else {
+                ((1 << count) - 1) << offset
+            };
+            let container = &mut self.data[index / BITS];
+            if value {
+                *container |= mask;
+            } else {
+                *container &= !mask;
+            }
+            index += count;
+        }
+    }
+
+    /// Flip the bit at `index`.
+    #[allow(dead_code)]
+    pub(crate) fn toggle(&mut self, index: usize) {
+        let (container, mask) = self.word_and_mask(index);
+        self.data[container] ^= mask;
+    }
+
+    /// Exchange the bits at `i` and `j`.
+    ///
+    /// # Panics
+    /// Panics if `i` or `j` is out of range.
+    #[allow(dead_code)]
+    pub(crate) fn swap(&mut self, i: usize, j: usize) {
+        let (a, b) = (self[i], self[j]);
+        if a != b {
+            self.set(i, b);
+            self.set(j, a);
+        }
+    }
+
+    /// Reverse the order of all bits, so that bit `i` moves to
+    /// `len() - 1 - i`.
+    #[allow(dead_code)]
+    pub(crate) fn reverse(&mut self) {
+        self.data.reverse();
+        for container in &mut self.data {
+            *container = container.reverse_bits();
+        }
+        // The unused bits are now at the start.
+        self.shr_assign(self.data.len() * BITS - self.length);
+    }
+
+    /// Return the bit at `index` or [`None`] if out of range.
+    #[allow(dead_code)]
+    pub(crate) fn get(&self, index: usize) -> Option<bool> {
+        (index < self.length).then(|| self[index])
+    }
+
+    /// Return the container index and the bit mask within the container for
+    /// `index`.
+    ///
+    /// # Panics
+    /// Panics if `index` is out of range.
+    fn word_and_mask(&self, index: usize) -> (usize, usize) {
+        assert!(index < self.length, "index out of range");
+        (index / BITS, 1 << (index % BITS))
+    }
+
+    /// Append `value` to the end.
+    fn push(&mut self, value: bool) {
+        if self.length == self.data.len() * BITS {
+            self.data.push(0);
+        }
+        self.length += 1;
+        self.set(self.length - 1, value);
+    }
+
+    /// Pack the bits into bytes, starting from the least significant bit.
+    ///
+    /// Unused bits of the last byte are zero.
+    pub(crate) fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![0; div_ceil(self.length, 8)];
+        for index in self.iter_ones() {
+            bytes[index / 8] |= 1 << (index % 8);
+        }
+        bytes
+    }
+
+    /// Unpack `length` bits from `bytes` produced by [`Self::to_bytes()`].
+    pub(crate) fn from_bytes(bytes: &[u8], length: usize) -> Result<Self> {
+        if bytes.len() != div_ceil(length, 8) {
+            return Err(Error::new_static(InvalidInput, "wrong number of bytes\0"));
+        }
+        let bit = |i: usize| bytes[i / 8] & (1 << (i % 8)) != 0;
+        if (length..8 * bytes.len()).any(bit) {
+            return Err(Error::new_static(InvalidInput, "unused bits set\0"));
+        }
+
+        Ok((0..length).map(bit).collect())
+    }
+
+    /// Create a bit vector with the same length and bits as `bits`.
+    #[allow(dead_code)]
+    pub(crate) fn from_bool_slice(bits: &[bool]) -> Self {
+        bits.iter().copied().collect()
+    }
+
+    /// Return all bits as booleans.
+    ///
+    /// Inverse of [`Self::from_bool_slice()`].
+    #[allow(dead_code)]
+    pub(crate) fn to_bool_vec(&self) -> Vec<bool> {
+        self.iter().collect()
+    }
+
     /// Copy data into self without allocating.
     ///
     /// # Panics
@@ -41,6 +176,117 @@ impl BitVec {
         self.data.copy_from_slice(&other.data);
     }
 
+    /// Same as [`Self::copy_from_bitvec()`] but first adapts the length to
+    /// `other` with [`Self::resize()`].
+    ///
+    /// Only allocates if the capacity does not suffice.
+    #[allow(dead_code)]
+    pub(crate) fn copy_resized_from(&mut self, other: &BitVec) {
+        self.resize(other.length);
+        self.copy_from_bitvec(other);
+    }
+
+    /// Bitwise AND `other` into `self`.
+    ///
+    /// # Panics
+    /// Panics if lengths mismatch.
+    pub(crate) fn band_assign(&mut self, other: &BitVec) {
+        self.zip_assign(other, |a, b| a & b);
+    }
+
+    /// Bitwise OR `other` into `self`.
+    ///
+    /// # Panics
+    /// Panics if lengths mismatch.
+    #[allow(dead_code)]
+    pub(crate) fn bor_assign(&mut self, other: &BitVec) {
+        self.zip_assign(other, |a, b| a | b);
+    }
+
+    /// Bitwise XOR `other` into `self`.
+    ///
+    /// # Panics
+    /// Panics if lengths mismatch.
+    #[allow(dead_code)]
+    pub(crate) fn bxor_assign(&mut self, other: &BitVec) {
+        self.zip_assign(other, |a, b| a ^ b);
+    }
+
+    /// Combine the containers of `self` and `other` with `op`.
+    ///
+    /// `op` must map zeros to zero to keep the unused bits zero.
+    fn zip_assign(&mut self, other: &BitVec, op: impl Fn(usize, usize) -> usize) {
+        assert_eq!(self.length, other.length, "BitVec lengths do not match");
+        for (a, &b) in self.data.iter_mut().zip(&other.data) {
+            *a = op(*a, b);
+        }
+    }
+
+    /// Move every bit `n` positions towards higher indices.
+    ///
+    /// Vacated bits become zero and bits moved past the end are dropped.
+    #[allow(dead_code)]
+    pub(crate) fn shl_assign(&mut self, n: usize) {
+        let (words, bits) = (n / BITS, n % BITS);
+        let source = |data: &[usize], i: usize| i.checked_sub(words).map_or(0, |i| data[i]);
+        for i in (0..self.data.len()).rev() {
+            let mut container = source(&self.data, i) << bits;
+            if bits != 0 && i != 0 {
+                container |= source(&self.data, i - 1) >> (BITS - bits);
+            }
+            self.data[i] = container;
+        }
+        self.clear_unused();
+    }
+
+    /// Move every bit `n` positions towards lower indices.
+    ///
+    /// Vacated bits become zero and bits moved before the start are dropped.
+    pub(crate) fn shr_assign(&mut self, n: usize) {
+        let (words, bits) = (n / BITS, n % BITS);
+        let source = |data: &[usize], i: usize| data.get(i.saturating_add(words)).map_or(0, |&c| c);
+        for i in 0..self.data.len() {
+            let mut container = source(&self.data, i) >> bits;
+            if bits != 0 {
+                container |= source(&self.data, i + 1) << (BITS - bits);
+            }
+            self.data[i] = container;
+        }
+    }
+
+    /// Set the unused bits of the last container to zero.
+    fn clear_unused(&mut self) {
+        if self.length % BITS != 0 {
+            if let Some(last) = self.data.last_mut() {
+                *last &= (1 << (self.length % BITS)) - 1;
+            }
+        }
+    }
+
+    /// Change the length to `new_length`.
+    ///
+    /// Existing bits are preserved up to the new length and new bits are
+    /// zero.
+    #[allow(dead_code)]
+    pub(crate) fn resize(&mut self, new_length: usize) {
+        self.data.resize(div_ceil(new_length, BITS), 0);
+        self.length = new_length;
+        self.clear_unused();
+    }
+
+    /// Shorten to `new_length` bits, keeping the allocated capacity.
+    ///
+    /// Does nothing if `new_length` is not smaller than the current length.
+    #[allow(dead_code)]
+    pub(crate) fn truncate(&mut self, new_length: usize) {
+        if new_length >= self.length {
+            return;
+        }
+        self.data.truncate(div_ceil(new_length, BITS));
+        self.length = new_length;
+        self.clear_unused();
+    }
+
     /// Fills `self` with zeros.
     pub(crate) fn reset(&mut self) {
         self.data.fill(0);
@@ -52,11 +298,63 @@ impl BitVec {
     }
 
     /// Is any bit set?
-    #[allow(dead_code)]
     pub(crate) fn any(&self) -> bool {
         self.data.iter().any(|&c| c != 0)
     }
 
+    /// Is no bit set?
+    pub(crate) fn none(&self) -> bool {
+        !self.any()
+    }
+
+    /// Is every bit set?
+    ///
+    /// This is true for empty bit vectors.
+    #[allow(dead_code)]
+    pub(crate) fn all(&self) -> bool {
+        let Some((&last, full)) = self.data.split_last() else {
+            return true;
+        };
+        let used = self.length % BITS;
+        let last_mask = if used == 0 {
+            usize::MAX
+        } else {
+            (1 << used) - 1
+        };
+        full.iter().all(|&c| c == usize::MAX) && last == last_mask
+    }
+
+    /// Number of set bits.
+    #[allow(dead_code)]
+    pub(crate) fn count_ones(&self) -> usize {
+        // Unused bits are zero and do not need to be masked.
+        self.data.iter().map(|c| c.count_ones() as usize).sum()
+    }
+
+    /// Number of unset bits.
+    ///
+    /// The unused bits of the last container are not counted.
+    #[allow(dead_code)]
+    pub(crate) fn count_zeros(&self) -> usize {
+        self.length - self.count_ones()
+    }
+
+    /// Iterate over the indices of all set bits in ascending order.
+    pub(crate) fn iter_ones(&self) -> impl Iterator<Item = usize> + '_ {
+        self.data.iter().enumerate().flat_map(|(i, &container)| {
+            let mut container = container;
+            iter::from_fn(move || {
+                if container == 0 {
+                    return None;
+                }
+                let bit = container.trailing_zeros() as usize;
+                // Clear the lowest set bit.
+                container &= container - 1;
+                Some(i * BITS + bit)
+            })
+        })
+    }
+
     pub(crate) fn iter(&self) -> Iter<'_> {
         Iter {
             bitvec: self,
@@ -69,8 +367,8 @@ impl Index<usize> for BitVec {
     type Output = bool;
 
     fn index(&self, index: usize) -> &Self::Output {
-        assert!(index < self.length, "index out of range");
-        if self.data[index / BITS] & (1 << (index % BITS)) != 0 {
+        let (container, mask) = self.word_and_mask(index);
+        if self.data[container] & mask != 0 {
             &true
         } else {
             &false
@@ -78,6 +376,34 @@ impl Index<usize> for BitVec {
     }
 }
 
+impl FromIterator<bool> for BitVec {
+    fn from_iter<T: IntoIterator<Item = bool>>(iter: T) -> Self {
+        let mut bitvec = BitVec::new(0);
+        bitvec.extend(iter);
+        bitvec
+    }
+}
+
+impl Extend<bool> for BitVec {
+    fn extend<T: IntoIterator<Item = bool>>(&mut self, iter: T) {
+        let iter = iter.into_iter();
+        self.data
+            .reserve(div_ceil(self.length + iter.size_hint().0, BITS) - self.data.len());
+        for value in iter {
+            self.push(value);
+        }
+    }
+}
+
+impl<'b> IntoIterator for &'b BitVec {
+    type Item = bool;
+    type IntoIter = Iter<'b>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
 impl Debug for BitVec {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_list().entries(self.iter().map(u8::from)).finish()
@@ -103,7 +429,532 @@ impl<'b> Iterator for Iter<'b> {
     }
 }
 
+/// Storage for the cells of a game board.
+///
+/// Boards with up to 128 bits, like the standard 7x6 one, are stored inline
+/// without heap allocation.
+/// Larger boards fall back to a [`BitVec`].
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub(crate) enum Board {
+    /// The bits and their number.
+    ///
+    /// Unused bits are always zero.
+    Inline(u128, usize),
+    Heap(BitVec),
+}
+
+impl Board {
+    /// Create a zeroed board with `length` bits, inline if they fit.
+    pub(crate) fn new(length: usize) -> Self {
+        if length <= u128::BITS as usize {
+            Self::Inline(0, length)
+        } else {
+            Self::Heap(BitVec::new(length))
+        }
+    }
+
+    pub(crate) fn set(&mut self, index: usize, value: bool) {
+        match self {
+            Self::Inline(bits, length) => {
+                assert!(index < *length, "index out of bounds");
+                if value {
+                    *bits |= 1 << index;
+                } else {
+                    *bits &= !(1 << index);
+                }
+            }
+            Self::Heap(bitvec) => bitvec.set(index, value),
+        }
+    }
+
+    /// See [`BitVec::to_bytes()`].
+    pub(crate) fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            Self::Inline(bits, length) => bits.to_le_bytes()[..div_ceil(*length, 8)].to_vec(),
+            Self::Heap(bitvec) => bitvec.to_bytes(),
+        }
+    }
+
+    /// Copy data into self, only allocating if `other` uses another storage.
+    ///
+    /// # Panics
+    /// Panics if lengths mismatch.
+    pub(crate) fn copy_from(&mut self, other: &Board) {
+        assert_eq!(self.len(), other.len(), "Board lengths do not match");
+        match (self, other) {
+            (Self::Heap(bitvec), Self::Heap(other)) => bitvec.copy_from_bitvec(other),
+            (board, other) => board.clone_from(other),
+        }
+    }
+
+    /// Same as [`Self::copy_from()`] but `other` may have another length.
+    ///
+    /// See [`BitVec::copy_resized_from()`].
+    #[allow(dead_code)]
+    pub(crate) fn copy_resized_from(&mut self, other: &Board) {
+        match (self, other) {
+            (Self::Heap(bitvec), Self::Heap(other)) => bitvec.copy_resized_from(other),
+            (board, other) => board.clone_from(other),
+        }
+    }
+
+    /// Fills `self` with zeros.
+    pub(crate) fn reset(&mut self) {
+        match self {
+            Self::Inline(bits, _) => *bits = 0,
+            Self::Heap(bitvec) => bitvec.reset(),
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        match self {
+            Self::Inline(_, length) => *length,
+            Self::Heap(bitvec) => bitvec.len(),
+        }
+    }
+
+    /// Is any bit set?
+    #[allow(dead_code)]
+    pub(crate) fn any(&self) -> bool {
+        match self {
+            Self::Inline(bits, _) => *bits != 0,
+            Self::Heap(bitvec) => bitvec.any(),
+        }
+    }
+}
+
+impl Index<usize> for Board {
+    type Output = bool;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        match self {
+            Self::Inline(bits, length) => {
+                assert!(index < *length, "index out of bounds");
+                if bits & 1 << index != 0 {
+                    &true
+                } else {
+                    &false
+                }
+            }
+            Self::Heap(bitvec) => &bitvec[index],
+        }
+    }
+}
+
 /// Ceiling division
 fn div_ceil(dividend: usize, divisor: usize) -> usize {
     dividend / divisor + usize::from(dividend % divisor != 0)
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    #[test]
+    fn board() {
+        let mut inline = Board::new(84);
+        assert!(matches!(inline, Board::Inline(..)));
+        let mut heap = Board::Heap(BitVec::new(84));
+        assert!(matches!(Board::new(128), Board::Inline(..)));
+        assert!(matches!(Board::new(129), Board::Heap(_)));
+
+        for i in [0, 1, 7, 63, 64, 83, 1, 64] {
+            inline.set(i, i % 2 == 0 || i == 63);
+            heap.set(i, i % 2 == 0 || i == 63);
+        }
+        inline.set(1, false);
+        heap.set(1, false);
+        for i in 0..84 {
+            assert_eq!(heap[i], inline[i], "bit {i}");
+        }
+        assert_eq!(heap.to_bytes(), inline.to_bytes());
+        assert_eq!(11, inline.to_bytes().len());
+        assert!(inline.any());
+
+        let mut copy = Board::new(84);
+        copy.copy_from(&inline);
+        assert_eq!(inline, copy);
+        copy.copy_from(&heap);
+        assert_eq!(heap, copy);
+        copy.reset();
+        assert!(!copy.any());
+        assert_eq!(84, copy.len());
+
+        let mut resized = Board::new(200);
+        resized.copy_resized_from(&inline);
+        assert_eq!(inline, resized);
+    }
+
+    #[test]
+    #[should_panic]
+    fn board_out_of_bounds() {
+        Board::new(84).set(84, true);
+    }
+
+    #[test]
+    fn count_ones() {
+        assert_eq!(0, BitVec::new(0).count_ones());
+        assert_eq!(0, BitVec::new(100).count_ones());
+
+        let mut bitvec = BitVec::new(100);
+        for i in 0..100 {
+            bitvec.set(i, true);
+        }
+        assert_eq!(100, bitvec.count_ones());
+
+        let mut bitvec = BitVec::new(150);
+        for i in [0, 3, 63, 64, 149] {
+            bitvec.set(i, true);
+        }
+        assert_eq!(5, bitvec.count_ones());
+    }
+
+    #[test]
+    fn all_none() {
+        let empty = BitVec::new(0);
+        assert!(empty.all());
+        assert!(empty.none());
+
+        for length in [1, 63, 64, 70, 128, 150] {
+            let mut bitvec = BitVec::new(length);
+            assert!(bitvec.none());
+            assert!(!bitvec.all());
+
+            bitvec.set(length - 1, true);
+            assert!(!bitvec.none());
+            assert_eq!(length == 1, bitvec.all());
+
+            for i in 0..length {
+                bitvec.set(i, true);
+            }
+            assert!(bitvec.all());
+            bitvec.set(0, false);
+            assert_eq!(length == 1, bitvec.none());
+            assert!(!bitvec.all());
+        }
+    }
+
+    #[test]
+    fn count_zeros() {
+        assert_eq!(0, BitVec::new(0).count_zeros());
+        assert_eq!(100, BitVec::new(100).count_zeros());
+
+        // The last container is only partially used.
+        let mut bitvec = BitVec::new(70);
+        bitvec.set(0, true);
+        bitvec.set(69, true);
+        assert_eq!(68, bitvec.count_zeros());
+        for i in 0..70 {
+            bitvec.set(i, true);
+        }
+        assert_eq!(0, bitvec.count_zeros());
+    }
+
+    #[test]
+    fn bitwise() {
+        let a = BitVec::from_bool_slice(&[true, true, false, false, true]);
+        let b = BitVec::from_bool_slice(&[true, false, true, false, true]);
+
+        let mut c = a.clone();
+        c.band_assign(&b);
+        assert_eq!(
+            BitVec::from_bool_slice(&[true, false, false, false, true]),
+            c
+        );
+        let mut c = a.clone();
+        c.bor_assign(&b);
+        assert_eq!(BitVec::from_bool_slice(&[true, true, true, false, true]), c);
+        let mut c = a.clone();
+        c.bxor_assign(&b);
+        assert_eq!(
+            BitVec::from_bool_slice(&[false, true, true, false, false]),
+            c
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "BitVec lengths do not match")]
+    fn bitwise_length_mismatch() {
+        BitVec::new(3).band_assign(&BitVec::new(4));
+    }
+
+    #[test]
+    fn toggle() {
+        let mut bitvec = BitVec::from_bool_slice(&[true, false, true]);
+        bitvec.toggle(1);
+        assert_eq!(BitVec::from_bool_slice(&[true, true, true]), bitvec);
+        bitvec.toggle(0);
+        bitvec.toggle(0);
+        assert_eq!(BitVec::from_bool_slice(&[true, true, true]), bitvec);
+        bitvec.toggle(1);
+        assert_eq!(BitVec::from_bool_slice(&[true, false, true]), bitvec);
+    }
+
+    #[test]
+    fn swap() {
+        let mut bitvec = BitVec::from_bool_slice(&[true, false, false]);
+        bitvec.swap(0, 0);
+        bitvec.swap(1, 2);
+        assert_eq!(BitVec::from_bool_slice(&[true, false, false]), bitvec);
+        bitvec.swap(0, 2);
+        assert_eq!(BitVec::from_bool_slice(&[false, false, true]), bitvec);
+
+        let mut bitvec = BitVec::new(130);
+        bitvec.set(3, true);
+        bitvec.swap(3, 129);
+        assert_eq!(vec![129], bitvec.iter_ones().collect::<Vec<_>>());
+    }
+
+    #[test]
+    #[should_panic]
+    fn swap_out_of_bounds() {
+        BitVec::new(3).swap(0, 3);
+    }
+
+    #[test]
+    fn reverse() {
+        let mut bitvec = BitVec::from_bool_slice(&[true, true, false, true, false]);
+        bitvec.reverse();
+        assert_eq!(
+            BitVec::from_bool_slice(&[false, true, false, true, true]),
+            bitvec
+        );
+
+        for length in [0, 1, 63, 64, 65, 150] {
+            let pattern = |i: usize| i % 3 == 0 || i % 7 == 1;
+            let mut bitvec: BitVec = (0..length).map(pattern).collect();
+            bitvec.reverse();
+            let expected: BitVec = (0..length).rev().map(pattern).collect();
+            // Equality also covers the unused bits.
+            assert_eq!(expected, bitvec, "{length}");
+            bitvec.reverse();
+            assert_eq!((0..length).map(pattern).collect::<BitVec>(), bitvec);
+        }
+    }
+
+    #[test]
+    fn set_range() {
+        for (start, end) in [(3, 10), (0, 64), (60, 70), (1, 149), (0, 150), (70, 70)] {
+            let mut bitvec = BitVec::new(150);
+            bitvec.set_range(start, end, true);
+            assert!(bitvec
+                .iter()
+                .eq((0..150).map(|i| (start..end).contains(&i))));
+
+            bitvec.set_range(0, 150, true);
+            bitvec.set_range(start, end, false);
+            assert!(bitvec
+                .iter()
+                .eq((0..150).map(|i| !(start..end).contains(&i))));
+        }
+
+        // The unused bits must stay zero.
+        let mut bitvec = BitVec::new(70);
+        bitvec.set_range(0, 70, true);
+        assert!(bitvec.all());
+        assert_eq!(70, bitvec.count_ones());
+    }
+
+    #[test]
+    #[should_panic(expected = "range out of bounds")]
+    fn set_range_out_of_bounds() {
+        BitVec::new(70).set_range(60, 71, true);
+    }
+
+    #[test]
+    fn get() {
+        let bitvec = BitVec::from_bool_slice(&[true, false, true]);
+        assert_eq!(Some(true), bitvec.get(0));
+        assert_eq!(Some(false), bitvec.get(1));
+        assert_eq!(Some(true), bitvec.get(2));
+        assert_eq!(None, bitvec.get(3));
+        assert_eq!(None, BitVec::new(0).get(0));
+        assert_eq!(None, BitVec::new(64).get(100));
+    }
+
+    #[test]
+    fn from_iter() {
+        let pattern = (0..150).map(|i| i % 3 == 0 || i == 64);
+        let bitvec: BitVec = pattern.clone().collect();
+        assert_eq!(150, bitvec.len());
+        assert!(bitvec.iter().eq(pattern.clone()));
+        assert_eq!(51, bitvec.count_ones());
+
+        let mut extended: BitVec = pattern.clone().take(60).collect();
+        extended.extend(pattern.clone().skip(60));
+        assert_eq!(bitvec, extended);
+
+        let empty: BitVec = iter::empty().collect();
+        assert_eq!(BitVec::new(0), empty);
+    }
+
+    #[test]
+    fn bool_slice() {
+        for length in [0, 1, 63, 64, 65, 130] {
+            let bools: Vec<_> = (0..length).map(|i| i % 3 != 1).collect();
+            let bitvec = BitVec::from_bool_slice(&bools);
+            assert_eq!(length, bitvec.len());
+            assert_eq!(bools, bitvec.to_bool_vec());
+            assert!(bitvec.iter().eq(bools.iter().copied()));
+
+            // Trailing bits must be zero.
+            let mut resized = bitvec.clone();
+            resized.resize(length + 70);
+            assert_eq!(bitvec.count_ones(), resized.count_ones());
+        }
+        assert_eq!(BitVec::new(5), BitVec::from_bool_slice(&[false; 5]));
+    }
+
+    #[test]
+    fn into_iter() {
+        let bools = [true, false, false, true, true];
+        let bitvec = BitVec::from_bool_slice(&bools);
+
+        let mut index = 0;
+        for bit in &bitvec {
+            assert_eq!(bools[index], bit);
+            index += 1;
+        }
+        assert_eq!(bools.len(), index);
+
+        let collected: Vec<bool> = (&bitvec).into_iter().collect();
+        assert_eq!(bools, *collected);
+    }
+
+    #[test]
+    fn iter_ones() {
+        for bitvec in [
+            BitVec::new(0),
+            BitVec::new(200),
+            (0..200).map(|i| i % 7 == 0 || i == 63 || i == 64).collect(),
+            (0..128).map(|_| true).collect(),
+        ] {
+            let expected: Vec<_> = bitvec
+                .iter()
+                .enumerate()
+                .filter(|&(_, b)| b)
+                .map(|(i, _)| i)
+                .collect();
+            assert_eq!(expected, bitvec.iter_ones().collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn shift() {
+        let pattern = |i: usize| i % 5 == 0 || i == 70;
+        let bitvec: BitVec = (0..150).map(pattern).collect();
+
+        for n in [0, 3, BITS, BITS + 7, 149, 150, 1000] {
+            let mut shifted = bitvec.clone();
+            shifted.shl_assign(n);
+            let expected: BitVec = (0..150).map(|i| i >= n && pattern(i - n)).collect();
+            assert_eq!(expected, shifted, "shl by {n}");
+
+            let mut shifted = bitvec.clone();
+            shifted.shr_assign(n);
+            let expected: BitVec = (0..150).map(|i| i + n < 150 && pattern(i + n)).collect();
+            assert_eq!(expected, shifted, "shr by {n}");
+        }
+    }
+
+    #[test]
+    fn bytes() {
+        for length in [0, 1, 8, 13, 64, 150] {
+            let bitvec: BitVec = (0..length).map(|i| i % 3 == 1 || i == 7).collect();
+            let bytes = bitvec.to_bytes();
+            assert_eq!(div_ceil(length, 8), bytes.len());
+            assert_eq!(bitvec, BitVec::from_bytes(&bytes, length).unwrap());
+        }
+
+        assert_eq!(
+            [0b1000_0101, 0b1],
+            *BitVec::from_bool_slice(&[true, false, true, false, false, false, false, true, true])
+                .to_bytes()
+        );
+        assert!(BitVec::from_bytes(&[0b1000_0101, 0b1], 9).is_ok());
+        assert!(BitVec::from_bytes(&[0b1000_0101, 0b11], 9).is_err());
+        assert!(BitVec::from_bytes(&[0b1000_0101, 0b1], 8).is_err());
+        assert!(BitVec::from_bytes(&[0b1000_0101], 9).is_err());
+    }
+
+    #[test]
+    fn hash() {
+        let mut set = HashSet::new();
+        set.insert(BitVec::from_bool_slice(&[true, false, true]));
+        set.insert([true, false, true].into_iter().collect());
+        assert_eq!(1, set.len());
+        set.insert(BitVec::from_bool_slice(&[true, false, true, false]));
+        assert_eq!(2, set.len());
+    }
+
+    #[test]
+    fn resize() {
+        let pattern = |i: usize| i % 3 == 0;
+        let mut bitvec: BitVec = (0..70).map(pattern).collect();
+
+        bitvec.resize(200);
+        assert_eq!(200, bitvec.len());
+        assert!(bitvec.iter().eq((0..200).map(|i| i < 70 && pattern(i))));
+
+        bitvec.resize(10);
+        assert_eq!((0..10).map(pattern).collect::<BitVec>(), bitvec);
+
+        // Bits cut off before must not reappear.
+        bitvec.resize(70);
+        assert!(bitvec.iter().eq((0..70).map(|i| i < 10 && pattern(i))));
+        bitvec.resize(0);
+        assert_eq!(BitVec::new(0), bitvec);
+    }
+
+    #[test]
+    fn copy_resized_from() {
+        let source: BitVec = (0..70).map(|i| i % 3 == 0).collect();
+        let mut target = BitVec::new(300);
+        target.set_range(0, 300, true);
+        let capacity = target.data.capacity();
+        target.copy_resized_from(&source);
+        assert_eq!(source, target);
+        assert_eq!(capacity, target.data.capacity());
+
+        let mut target = BitVec::new(3);
+        target.copy_resized_from(&source);
+        assert_eq!(source, target);
+    }
+
+    #[test]
+    fn with_capacity() {
+        let mut bitvec = BitVec::with_capacity(200);
+        assert_eq!(BitVec::new(0), bitvec);
+        let capacity = bitvec.data.capacity();
+        assert!(capacity * BITS >= 200);
+
+        bitvec.extend((0..200).map(|i| i % 2 == 0));
+        assert_eq!(200, bitvec.len());
+        assert_eq!(capacity, bitvec.data.capacity());
+    }
+
+    #[test]
+    fn truncate() {
+        let mut bitvec = BitVec::new(150);
+        bitvec.set_range(0, 150, true);
+        let capacity = bitvec.data.capacity();
+
+        bitvec.truncate(200);
+        assert_eq!(150, bitvec.len());
+        bitvec.truncate(70);
+        assert_eq!(70, bitvec.len());
+        assert_eq!(div_ceil(70, BITS), bitvec.data.len());
+        assert_eq!(70, bitvec.count_ones());
+        assert!(bitvec.all());
+
+        // Bits cut off must not reappear and the memory is reused.
+        bitvec.resize(150);
+        assert!(bitvec.iter().eq((0..150).map(|i| i < 70)));
+        assert_eq!(capacity, bitvec.data.capacity());
+
+        bitvec.truncate(0);
+        assert_eq!(BitVec::new(0), bitvec);
+    }
+}
@@ -1,119 +1,425 @@
 //! _mirabel_ frontend plugin for _Connect Four_.
 
 use std::{
+    collections::VecDeque,
+    f32::consts::PI,
     ops::{Deref, DerefMut},
     time::{Duration, Instant},
 };
 
 use mirabel::{
     cstr,
-    error::{ErrorCode, Result},
+    error::{Error, ErrorCode, Result},
     event::{EventAny, EventEnum, MoveData},
     frontend::{
         frontend_display_data, frontend_feature_flags,
-        skia::{Color4f, Matrix, Paint, Rect},
+        skia::{Canvas, Color4f, Font, Matrix, Paint, PaintStyle, Rect, Typeface},
         Context, FrontendMethods, GameInfo, Metadata,
     },
     game::{player_id, semver, GameMethods, SYNC_CTR_DEFAULT},
     game_init::GameInit,
+    imgui::{ImGuiThin_Checkbox, ImGuiThin_ColorEdit4, ImGuiThin_SliderFloat},
     plugin_get_frontend_methods,
-    sdl_event::{sdl_button_mask, SDLEventEnum, SDL_BUTTON_LEFT},
+    sdl_event::{
+        sdl_button_mask, SDLEventEnum, SDL_GetModState, SDL_Keymod, SDL_Keymod_KMOD_SHIFT,
+        SDL_BUTTON_LEFT, SDL_BUTTON_RIGHT,
+    },
     CodeResult, MoveDataSync, ValidCStr,
 };
 
-use crate::game::{
-    player_from_id, player_to_id, ConnectFour, Pos, State, GAME_NAME, IMPL_NAME, VARIANT_NAME,
+use crate::{
+    game::{
+        player_from_id, player_to_id, ConnectFour, GameResult, Pos, State, GAME_NAME, IMPL_NAME,
+        VARIANT_NAME,
+    },
+    style::{self, Rgb, FRAME_WIDTH, MARGIN},
 };
 
 /// Background color.
-const BACKGROUND: Color4f = Color4f::new(201. / 255., 144. / 255., 73. / 255., 1.);
+const BACKGROUND: Color4f = color(style::BACKGROUND);
 /// Frame color.
-const FRAME: Color4f = Color4f::new(161. / 255., 119. / 255., 67. / 255., 1.);
+const FRAME: Color4f = color(style::FRAME);
 /// Chip color for X.
-const CHIP_X: Color4f = Color4f::new(240. / 255., 217. / 255., 181. / 255., 1.);
+const CHIP_X: Color4f = color(style::CHIP_X);
 /// Chip color for O.
-const CHIP_O: Color4f = Color4f::new(199. / 255., 36. / 255., 73. / 255., 1.);
-
-/// Width of a frame bar.
-const FRAME_WIDTH: f32 = 0.1;
-/// Minimum margin around the frame.
-const MARGIN: f32 = 0.1;
+const CHIP_O: Color4f = color(style::CHIP_O);
+/// Chip color for Z.
+const CHIP_Z: Color4f = color(style::CHIP_Z);
+/// Chip color for Y.
+const CHIP_Y: Color4f = color(style::CHIP_Y);
+/// Color of the ring marking the last move.
+const MARKER: Color4f = Color4f::new(64. / 255., 47. / 255., 27. / 255., 1.);
+
+/// Color of the rings marking winning moves.
+const THREAT: Color4f = Color4f::new(1., 223. / 255., 64. / 255., 1.);
+/// Radius of the rings marking winning moves.
+const THREAT_RADIUS: f32 = 0.42;
+/// Stroke width of the rings marking winning moves.
+const THREAT_WIDTH: f32 = 0.06;
+/// Stroke width of the translucent glow around these rings.
+const THREAT_GLOW_WIDTH: f32 = 0.18;
+/// Opacity of the glow around these rings.
+const THREAT_GLOW_ALPHA: f32 = 0.35;
+
+/// Color of the tint over the targeted column.
+const TINT: Color4f = Color4f::new(1., 1., 1., 0.15);
+/// Width of the guide line through the center of the targeted column.
+const GUIDE_WIDTH: f32 = 0.04;
+/// Opacity of the guide line.
+const GUIDE_ALPHA: f32 = 0.6;
+/// Opacity of the ghost preview chip shown while input is disabled.
+///
+/// Low enough to not be mistaken for a falling or settled chip.
+const GHOST_ALPHA: f32 = 0.3;
+/// Upper bound (exclusive) of the frame width so that cells stay visible.
+const MAX_FRAME_WIDTH: f32 = 0.5;
+/// Default radius of the chips.
+///
+/// Chips of this radius fill their cells completely.
+const CHIP_RADIUS: f32 = 0.5;
+/// Color of the area behind the game-over text.
+const BANNER: Color4f = Color4f::new(0., 0., 0., 0.5);
+/// Color of the game-over text.
+const BANNER_TEXT: Color4f = Color4f::new(1., 1., 1., 1.);
+
+/// Half the size of the cross above full columns.
+const CROSS_RADIUS: f32 = 0.3;
+/// Stroke width of the cross above full columns.
+const CROSS_WIDTH: f32 = 0.1;
+/// Opacity of the cross above full columns.
+const CROSS_ALPHA: f32 = 0.5;
+/// Size of the symbols on chips relative to the chip radius.
+const SYMBOL_SIZE: f32 = 0.5;
+/// Stroke width of the symbols on chips relative to the chip radius.
+const SYMBOL_WIDTH: f32 = 0.2;
+/// Font size of the move numbers on chips, in cells.
+const LABEL_SIZE: f32 = 0.4;
+/// Radius of the turn indicator relative to the display size.
+const INDICATOR_SIZE: f32 = 0.03;
+/// Radius of the ring marking the last move.
+const MARKER_RADIUS: f32 = 0.25;
+/// Stroke width of the ring marking the last move.
+const MARKER_WIDTH: f32 = 0.08;
 /// Height above the frame from which chips drop.
 const DROP_HEIGHT: f32 = 1.2;
-/// How long should an animation take at most.
+/// Default for how long an animation should take at most.
 const ANIMATION_SPEED: Duration = Duration::from_millis(500);
+/// Upper end of the slider for the animation speed in seconds.
+const MAX_ANIMATION_SPEED: f32 = 2.;
+/// Distance in cells which a chip falls in the animation duration with
+/// [`AnimationMode::ConstantSpeed`].
+///
+/// This is the drop height of a board with the default height of six.
+const STANDARD_DROP: f32 = 5. + DROP_HEIGHT;
+/// Maximum height of the bounce after a chip lands, in cells.
+const BOUNCE_HEIGHT: f32 = 0.15;
+/// Duration of the bounce relative to the animation speed.
+const BOUNCE_TIME: f32 = 0.3;
+/// Key which restarts the game (see [`Frontend::restarts()`]).
+const RESTART_KEY: char = 'r';
 
 /// Container for the state of the frontend.
 struct Frontend {
     /// The currently running game if any.
     game: Option<Game>,
     mouse: Mouse,
-    /// The currently active animation if any.
-    animation: Option<Animation>,
+    /// Queue of animations which are played one after another.
+    ///
+    /// Only the front animation is running.
+    animations: VecDeque<Animation>,
     /// Is user input disabled?
     disabled: bool,
     /// Keep track of the current sync counter.
     sync_ctr: u64,
+    /// The cell of the most recently played chip if known.
+    last_move: Option<Pos>,
+    colors: Colors,
+    /// How long should an animation take at most.
+    ///
+    /// Chips are placed instantly for a zero duration.
+    animation_speed: Duration,
+    animation_mode: AnimationMode,
+    /// Column last selected using the keyboard.
+    ///
+    /// Reset by mouse movement.
+    selected: Option<u16>,
+    /// Draw symbols on chips so that players can be told apart without color.
+    symbols: bool,
+    /// Label chips with the number of the move which dropped them.
+    move_numbers: bool,
+    /// Mirror the board horizontally while `O` is to move.
+    mirror_for_o: bool,
+    /// Mark the cells where the player to move would win immediately.
+    threats: bool,
+    /// Show a faint preview chip where the user points while input is
+    /// disabled.
+    ghost_preview: bool,
+    /// See [`CachedMatrix::get()`].
+    matrix: Option<CachedMatrix>,
+    /// Radius of the chips in `(0, 0.5]`.
+    chip_radius: f32,
+    /// Width of the frame bars in `[0, 0.5)`.
+    frame_width: f32,
 }
 
 impl Frontend {
     /// Reset whole frontend including the game.
     fn reset(&mut self) {
         self.game = None;
+        self.animations.clear();
         self.clear();
         self.mouse.current = None;
+        self.last_move = None;
+        self.selected = None;
+        self.matrix = None;
     }
 
     /// Clear current user input.
     ///
     /// This should be called when an external event is received.
+    /// Input stays disabled while animations are queued.
     fn clear(&mut self) {
         self.mouse.clear();
-        self.disabled = false;
-        self.animation = None;
+        self.disabled = !self.animations.is_empty();
     }
 
     /// Get the column corresponding with this location if any.
     ///
     /// Only returns the column if such a move would be possible.
-    fn get_column(&self, x: f32) -> Option<u8> {
+    fn get_column(&self, x: f32) -> Option<u16> {
+        self.column_at(x).and_then(|c| self.valid_column(c))
+    }
+
+    /// Get the existing column corresponding with this location if any.
+    fn column_at(&self, x: f32) -> Option<u16> {
         let Some(ref game) = self.game else {return None;};
         let rounded = x.round();
 
-        if rounded < 0. || game.width() <= rounded as u8 {
+        if rounded < 0. || game.width() <= rounded as u16 {
             None
         } else {
-            Some(rounded as u8)
+            Some(rounded as u16)
         }
-        .filter(|&c| game.possible_move(c))
     }
 
-    /// Calculate the column above which to show a preview, if any.
-    fn preview(&self) -> Option<u8> {
+    /// Return `column` if it exists and a move there would be possible.
+    fn valid_column(&self, column: u16) -> Option<u16> {
+        let Some(ref game) = self.game else {return None;};
+
+        Some(column).filter(|&c| c < game.width() && game.possible_move(c))
+    }
+
+    /// Should the board currently be shown mirrored (see
+    /// [`Self::mirror_for_o`])?
+    ///
+    /// Only the presentation is mirrored, not the game itself.
+    fn mirrored(&self, game: &Game) -> bool {
+        self.mirror_for_o && game.result() == GameResult::Ongoing && game.turn() == 1
+    }
+
+    /// Calculate the existing column which the user points at, if any.
+    fn hovered(&self) -> Option<u16> {
         if self.disabled {
             return None;
         }
 
+        self.pointed()
+    }
+
+    /// Like [`Self::hovered()`] but ignores whether input is disabled.
+    fn pointed(&self) -> Option<u16> {
+        if let Some(column) = self.selected {
+            let Some(ref game) = self.game else {return None;};
+            return Some(column).filter(|&c| c < game.width());
+        }
         let Some((x, _)) = self.mouse.clicked.or(self.mouse.current) else { return None; };
-        self.get_column(x)
+        self.column_at(x)
+    }
+
+    /// Calculate the column above which to show a preview, if any.
+    fn preview(&self) -> Option<u16> {
+        self.hovered().and_then(|c| self.valid_column(c))
+    }
+
+    /// Calculate the column through which to draw a guide line, if any.
+    ///
+    /// This is the column a click would select, so the guide shows how the
+    /// pointer position is rounded.
+    /// No guide is shown while a chip is falling.
+    fn guide(&self) -> Option<u16> {
+        self.preview().filter(|_| self.animations.is_empty())
+    }
+
+    /// Calculate the column above which to show a ghost preview, if any.
+    ///
+    /// Ghosts are only shown while input is disabled and only if
+    /// [`Self::ghost_preview`] is set.
+    fn ghost(&self) -> Option<u16> {
+        if !self.ghost_preview || !self.disabled {
+            return None;
+        }
+
+        self.pointed().and_then(|c| self.valid_column(c))
+    }
+
+    /// Send a move dropping a chip into `column` and start its animation.
+    ///
+    /// An `instant` drop skips the falling part of the animation.
+    fn send_move(&mut self, ctx: &mut Context<Self>, column: u16, instant: bool) {
+        let Some(ref game) = self.game else { return; };
+        let Some(target) = game.landing_cell(column) else { return; };
+
+        ctx.outbox.push(&mut EventAny::new_game_move(
+            game.player_id(),
+            MoveDataSync {
+                md: MoveData::MoveCode(column.into()),
+                sync_ctr: self.sync_ctr,
+            },
+        ));
+        self.disabled = true;
+        let mut animation = Animation::new(game.drop_height(), target, game.turn());
+        animation.instant = instant;
+        self.animations.push_back(animation);
+        self.last_move = Some(target);
+    }
+
+    /// Does pressing the key with keycode `sym` restart the game?
+    ///
+    /// [`RESTART_KEY`] only restarts a loaded game once it is over, so that a
+    /// running game is not thrown away by accident.
+    /// Until then, the key may select a column (see [`key_to_column()`]).
+    fn restarts(&self, sym: i32) -> bool {
+        let Some(ref game) = self.game else { return false; };
+        game.is_terminal() && u32::try_from(sym).ok().and_then(char::from_u32) == Some(RESTART_KEY)
+    }
+
+    /// Restart the current game with the same options.
+    ///
+    /// This emits a game state event without a state, which resets the game
+    /// to its initial position, including any handicap stones.
+    /// The frontend itself only resets once the event comes back through
+    /// [`FrontendMethods::process_event()`].
+    fn restart(&self, ctx: &mut Context<Self>) {
+        if self.game.is_none() {
+            return;
+        }
+
+        ctx.outbox.push(&mut EventAny::new_game_state(None));
+    }
+
+    /// Show the widgets for changing the options at runtime on `ui`.
+    ///
+    /// Changes take effect with the next frame.
+    fn edit_options(&mut self, ui: &mut impl OptionsUi) {
+        let colors = &mut self.colors;
+        ui.color("Background\0", &mut colors.background);
+        ui.color("Frame\0", &mut colors.frame);
+        ui.color("Chip X\0", &mut colors.chip_x);
+        ui.color("Chip O\0", &mut colors.chip_o);
+
+        ui.checkbox("Symbols on chips\0", &mut self.symbols);
+        ui.checkbox("Move numbers\0", &mut self.move_numbers);
+        ui.checkbox("Mirror board for O\0", &mut self.mirror_for_o);
+        ui.checkbox("Mark threats\0", &mut self.threats);
+        ui.checkbox("Ghost preview\0", &mut self.ghost_preview);
+
+        let mut seconds = self.animation_speed.as_secs_f32();
+        if ui.slider(
+            "Animation duration (s)\0",
+            &mut seconds,
+            0.,
+            MAX_ANIMATION_SPEED,
+        ) {
+            // Negative durations make chips snap into place.
+            if let Ok(speed) = Duration::try_from_secs_f32(seconds.max(0.)) {
+                self.animation_speed = speed;
+            }
+        }
+        let mut constant_speed = self.animation_mode == AnimationMode::ConstantSpeed;
+        if ui.checkbox("Constant falling speed\0", &mut constant_speed) {
+            self.animation_mode = if constant_speed {
+                AnimationMode::ConstantSpeed
+            } else {
+                AnimationMode::ConstantDuration
+            };
+        }
+
+        let mut radius = self.chip_radius;
+        if ui.slider("Chip radius\0", &mut radius, 0., CHIP_RADIUS) {
+            self.chip_radius = clamp_chip_radius(radius);
+        }
+        let mut width = self.frame_width;
+        if ui.slider("Frame width\0", &mut width, 0., MAX_FRAME_WIDTH) {
+            self.frame_width = clamp_frame_width(width);
+        }
+    }
+
+    /// Draw a chip of `player` at `center`.
+    ///
+    /// Also draws a symbol on top if [`Self::symbols`] is set.
+    fn draw_chip(&self, c: &mut Canvas, center: (f32, f32), radius: f32, player: u8) {
+        c.draw_circle(center, radius, &self.colors.chip(player));
+        if !self.symbols {
+            return;
+        }
+
+        let mut paint = Paint::new(MARKER, None);
+        paint
+            .set_style(PaintStyle::Stroke)
+            .set_stroke_width(SYMBOL_WIDTH * radius);
+        let size = SYMBOL_SIZE * radius;
+        let (x, y) = center;
+        match player {
+            0 => {
+                c.draw_line((x - size, y - size), (x + size, y + size), &paint);
+                c.draw_line((x - size, y + size), (x + size, y - size), &paint);
+            }
+            1 => {
+                c.draw_circle(center, size, &paint);
+            }
+            2 => {
+                c.draw_line((x - size, y + size), (x + size, y + size), &paint);
+                c.draw_line((x + size, y + size), (x - size, y - size), &paint);
+                c.draw_line((x - size, y - size), (x + size, y - size), &paint);
+            }
+            _ => {
+                c.draw_line((x - size, y + size), center, &paint);
+                c.draw_line((x + size, y + size), center, &paint);
+                c.draw_line(center, (x, y - size), &paint);
+            }
+        }
     }
 }
 
 impl FrontendMethods for Frontend {
-    type Options = ();
+    type Options = Options;
 
-    fn create(_options: Option<&Self::Options>) -> Result<Self> {
+    fn create(options: Option<&Self::Options>) -> Result<Self> {
+        let options = options.copied().unwrap_or_default();
         Ok(Self {
             game: Default::default(),
             mouse: Default::default(),
-            animation: Default::default(),
+            animations: Default::default(),
             disabled: Default::default(),
             sync_ctr: SYNC_CTR_DEFAULT,
+            last_move: Default::default(),
+            colors: Colors::new(&options),
+            animation_speed: options.animation_speed.unwrap_or(ANIMATION_SPEED),
+            animation_mode: options.animation_mode,
+            selected: Default::default(),
+            symbols: options.symbols,
+            move_numbers: options.move_numbers,
+            mirror_for_o: options.mirror_for_o,
+            threats: options.threats,
+            ghost_preview: options.ghost_preview,
+            matrix: Default::default(),
+            chip_radius: options.chip_radius(),
+            frame_width: options.frame_width(),
         })
     }
 
     fn runtime_opts_display(&mut self, _ctx: Context<Self>) -> Result<()> {
-        // No runtime options.
+        self.edit_options(&mut ImGui);
         Ok(())
     }
 
@@ -125,9 +431,12 @@ impl FrontendMethods for Frontend {
             }
             EventEnum::GameUnload(_) => self.reset(),
             EventEnum::GameState(e) => {
+                self.animations.clear();
                 self.clear();
+                self.last_move = None;
                 if let Some(ref mut g) = self.game {
                     g.import_state(e.state.map(ValidCStr::into))?;
+                    self.last_move = g.last_stone();
                 }
             }
             EventEnum::GameMove(e) => {
@@ -135,26 +444,32 @@ impl FrontendMethods for Frontend {
                 if let Some(ref mut g) = self.game {
                     let MoveData::MoveCode(code) = e.data.md else { panic!("unexpected big move") };
                     let column = code.try_into().expect("unexpectedly large move code");
-                    if let Some(ref mut a) = self.animation {
-                        if e.player == g.player_id() && a.target.0 == column {
-                            a.started = true;
-                        } else {
-                            self.animation = None;
+                    let Some(target) = g.landing_cell(column) else {
+                        return Err(Error::new_static(
+                            ErrorCode::InvalidInput,
+                            "move into full column\0",
+                        ));
+                    };
+                    // Only our own move can be queued without having started.
+                    let own = e.player == g.player_id();
+                    match self.animations.back_mut() {
+                        Some(a) if !a.started && own && a.target.0 == column => a.started = true,
+                        back => {
+                            if matches!(back, Some(a) if !a.started) {
+                                self.animations.pop_back();
+                            }
+                            let mut animation =
+                                Animation::new(g.drop_height(), target, player_from_id(e.player));
+                            animation.started = true;
+                            self.animations.push_back(animation);
                         }
-                    } else {
-                        let mut animation = Animation::new(
-                            g.drop_height(),
-                            (column, g.free_cell(column)),
-                            player_from_id(e.player),
-                        );
-                        animation.started = true;
-                        self.animation = Some(animation);
                     }
 
                     // We can simply use the sync counter of this move event to
                     // calculate the next one for our own move.
                     self.sync_ctr = e.data.sync_ctr + 1;
                     self.disabled = true;
+                    self.last_move = Some(target);
                     // ConnectFour ignores the sync counter anyway.
                     g.make_move(e.player, MoveDataSync::with_default(&code))?;
                 }
@@ -166,14 +481,39 @@ impl FrontendMethods for Frontend {
     }
 
     fn process_input(&mut self, mut ctx: Context<Self>, event: SDLEventEnum) -> Result<()> {
-        let mouse = &mut self.mouse;
+        if let SDLEventEnum::KeyDown(ref e) = event {
+            if self.restarts(e.keysym.sym) {
+                if e.repeat == 0 {
+                    self.restart(&mut ctx);
+                }
+                return Ok(());
+            }
+            let Some(column) = key_to_column(e.keysym.sym) else { return Ok(()); };
+            self.selected = Some(column);
+            if e.repeat == 0 && !self.disabled && self.valid_column(column).is_some() {
+                let instant = instant_drop(e.keysym.mod_.into());
+                self.send_move(&mut ctx, column, instant);
+            }
+            return Ok(());
+        }
+
         let Some(ref game) = self.game else { return Ok(()); };
+        let mirrored = self.mirrored(game);
+        let mouse = &mut self.mouse;
 
-        let matrix = calc_matrix(game, ctx.display_data)
-            .invert()
-            .expect("transformation matrix not invertible");
+        // Ignore pointer input while the window is collapsed.
+        let display = ctx.display_data;
+        if display.w <= 0. || display.h <= 0. {
+            return Ok(());
+        }
+        // The inverse of a mirrored matrix maps to the logical columns.
+        let frame_width = self.frame_width;
+        let cached = CachedMatrix::get(&mut self.matrix, game, display, mirrored, frame_width);
+        let Some(matrix) = cached.inverse else { return Ok(()); };
+        let mut instant = false;
         let clicked = match event {
             SDLEventEnum::MouseMotion(e) => {
+                self.selected = None;
                 let point = matrix.map_point((e.x, e.y));
                 mouse.update_position(point.x, point.y);
                 mouse.update(sdl_button_mask(SDL_BUTTON_LEFT) & e.state != 0);
@@ -184,8 +524,11 @@ impl FrontendMethods for Frontend {
                 let point = matrix.map_point((e.x, e.y));
                 mouse.update_position(point.x, point.y);
 
-                if !self.disabled && u32::from(e.button) == SDL_BUTTON_LEFT {
-                    mouse.update_down();
+                match u32::from(e.button) {
+                    SDL_BUTTON_LEFT if !self.disabled => mouse.update_down(),
+                    // Right-clicking cancels a pending left click.
+                    SDL_BUTTON_RIGHT => mouse.clear(),
+                    _ => {}
                 }
 
                 None
@@ -195,11 +538,32 @@ impl FrontendMethods for Frontend {
                 mouse.update_position(point.x, point.y);
 
                 if !self.disabled && u32::from(e.button) == SDL_BUTTON_LEFT {
+                    // SAFETY: This only reads the keyboard state kept by SDL.
+                    instant = instant_drop(unsafe { SDL_GetModState() });
                     mouse.update_up()
                 } else {
                     None
                 }
             }
+            SDLEventEnum::FingerDown(e) => {
+                self.selected = None;
+                let point = matrix.map_point(touch_to_window(e.x, e.y, ctx.display_data));
+                mouse.finger_down(point.x, point.y, !self.disabled);
+
+                None
+            }
+            SDLEventEnum::FingerMotion(e) => {
+                let point = matrix.map_point(touch_to_window(e.x, e.y, ctx.display_data));
+                mouse.finger_motion(point.x, point.y);
+
+                None
+            }
+            SDLEventEnum::FingerUp(e) => {
+                let point = matrix.map_point(touch_to_window(e.x, e.y, ctx.display_data));
+                let clicked = mouse.finger_up(point.x, point.y);
+
+                clicked.filter(|_| !self.disabled)
+            }
             _ => None,
         };
 
@@ -211,33 +575,26 @@ impl FrontendMethods for Frontend {
             return Ok(());
         }
 
-        ctx.outbox.push(&mut EventAny::new_game_move(
-            game.player_id(),
-            MoveDataSync {
-                md: MoveData::MoveCode(column.into()),
-                sync_ctr: self.sync_ctr,
-            },
-        ));
-        self.disabled = true;
-        self.animation = Some(Animation::new(
-            game.drop_height(),
-            (column, game.free_cell(column)),
-            game.turn(),
-        ));
+        self.send_move(&mut ctx, column, instant);
 
         Ok(())
     }
 
     fn update(&mut self, _ctx: Context<Self>) -> Result<()> {
-        let max_drop = match self.game {
-            Some(ref g) => g.drop_height(),
+        let distance = match self.game {
+            Some(ref g) => self.animation_mode.distance(g.drop_height()),
             None => return Ok(()),
         };
 
-        if let Some(ref mut a) = self.animation {
-            if a.update(max_drop) {
-                self.animation = None;
-                self.disabled = false;
+        // Input stays disabled until the queue drains.
+        // Instant drops also finish here, on the first update after their
+        // move was confirmed, so they re-enable input like any other drop.
+        if let Some(a) = self.animations.front_mut() {
+            if a.update(distance, self.animation_speed, Instant::now()) {
+                self.animations.pop_front();
+                if self.animations.is_empty() {
+                    self.disabled = false;
+                }
             }
         }
 
@@ -246,64 +603,200 @@ impl FrontendMethods for Frontend {
 
     fn render(&mut self, mut ctx: Context<Self>) -> Result<()> {
         let c = ctx.canvas.get();
-        c.clear(BACKGROUND);
+        c.clear(self.colors.background);
 
         let Some(ref game) = self.game else {return Ok(());};
-        let matrix = &calc_matrix(game, ctx.display_data);
+        let (mirrored, frame_width) = (self.mirrored(game), self.frame_width);
+        let matrix = &CachedMatrix::get(
+            &mut self.matrix,
+            game,
+            ctx.display_data,
+            mirrored,
+            frame_width,
+        )
+        .matrix;
         c.set_matrix(&matrix.into());
 
+        // Tint the targeted column and draw a guide line through its center
+        // unless a chip is still falling.
+        if let Some(col) = self.guide() {
+            let x = f32::from(col);
+            c.draw_rect(
+                Rect::from_xywh(x - 0.5, -0.5, 1., f32::from(game.height())),
+                &Paint::new(TINT, None),
+            );
+            let mut paint = Paint::new(MARKER, None);
+            paint
+                .set_style(PaintStyle::Stroke)
+                .set_stroke_width(GUIDE_WIDTH)
+                .set_alpha_f(GUIDE_ALPHA);
+            c.draw_line((x, -0.5), (x, game.drop_height()), &paint);
+        }
         // Draw chips.
+        // Every queued move has already been applied to the game, so its chip
+        // is part of `chips()`. Skip all queued targets since these chips are
+        // either falling right now or have not even started falling.
         for (x, y, player) in game.chips() {
-            if let Some(ref a) = self.animation {
-                if a.target == (x, y) {
-                    continue;
-                }
+            if self.animations.iter().any(|a| a.target == (x, y)) {
+                continue;
             }
 
-            c.draw_circle((f32::from(x), f32::from(y)), 0.5, &turn_to_paint(player));
+            self.draw_chip(c, (f32::from(x), f32::from(y)), self.chip_radius, player);
+        }
+        // Draw blocked cells.
+        let paint = Paint::new(self.colors.frame, None);
+        for (x, y) in game.blocks() {
+            c.draw_rect(
+                Rect::from_xywh(f32::from(x) - 0.5, f32::from(y) - 0.5, 1., 1.),
+                &paint,
+            );
         }
         // Draw animated chip.
-        if let Some(ref a) = self.animation {
-            c.draw_circle(a.position(), 0.5, &turn_to_paint(a.player));
+        if let Some(a) = self.animations.front() {
+            self.draw_chip(c, a.position(), self.chip_radius, a.player);
         }
-        // Draw input preview.
+        // Draw last move marker unless the chip is still falling.
+        if let Some(pos) = self.last_move {
+            if !self.animations.iter().any(|a| a.target == pos) {
+                let mut paint = Paint::new(MARKER, None);
+                paint
+                    .set_style(PaintStyle::Stroke)
+                    .set_stroke_width(MARKER_WIDTH);
+                c.draw_circle((f32::from(pos.0), f32::from(pos.1)), MARKER_RADIUS, &paint);
+            }
+        }
+        // Draw threats unless a chip is falling or the game is over.
+        if self.threats && self.animations.is_empty() && game.result() == GameResult::Ongoing {
+            let mut glow = Paint::new(THREAT, None);
+            glow.set_style(PaintStyle::Stroke)
+                .set_stroke_width(THREAT_GLOW_WIDTH)
+                .set_alpha_f(THREAT_GLOW_ALPHA);
+            let mut ring = Paint::new(THREAT, None);
+            ring.set_style(PaintStyle::Stroke)
+                .set_stroke_width(THREAT_WIDTH);
+            for (x, y) in game.threats() {
+                let center = (f32::from(x), f32::from(y));
+                c.draw_circle(center, THREAT_RADIUS, &glow);
+                c.draw_circle(center, THREAT_RADIUS, &ring);
+            }
+        }
+        // Draw input preview or a cross above full columns.
         if let Some(col) = self.preview() {
-            c.draw_circle(
-                (f32::from(col), game.drop_height()),
-                0.5,
-                &turn_to_paint(game.turn()),
-            );
+            let center = (f32::from(col), game.drop_height());
+            self.draw_chip(c, center, self.chip_radius, game.turn());
+        } else if let Some(col) = self.ghost() {
+            let mut paint = self.colors.chip(game.turn());
+            paint.set_alpha_f(GHOST_ALPHA);
+            let center = (f32::from(col), game.drop_height());
+            c.draw_circle(center, self.chip_radius, &paint);
+        } else if let Some(col) = self.hovered() {
+            if game.result() == GameResult::Ongoing {
+                let mut paint = Paint::new(self.colors.frame, None);
+                paint
+                    .set_style(PaintStyle::Stroke)
+                    .set_stroke_width(CROSS_WIDTH)
+                    .set_alpha_f(CROSS_ALPHA);
+                let (x, y) = (f32::from(col), game.drop_height());
+                let r = CROSS_RADIUS;
+                c.draw_line((x - r, y - r), (x + r, y + r), &paint);
+                c.draw_line((x - r, y + r), (x + r, y - r), &paint);
+            }
         }
 
         // Draw frame.
-        let paint = Paint::new(FRAME, None);
-        let mut x = -0.5 - 0.5 * FRAME_WIDTH;
+        let paint = Paint::new(self.colors.frame, None);
+        let frame_width = self.frame_width;
+        let mut x = -0.5 - 0.5 * frame_width;
         for _ in 0..=game.width() {
             c.draw_rect(
                 Rect::from_xywh(
                     x,
-                    -0.5 - 0.5 * FRAME_WIDTH,
-                    FRAME_WIDTH,
-                    f32::from(game.height()) + FRAME_WIDTH,
+                    -0.5 - 0.5 * frame_width,
+                    frame_width,
+                    f32::from(game.height()) + frame_width,
                 ),
                 &paint,
             );
             x += 1.;
         }
-        let mut y = -0.5 - 0.5 * FRAME_WIDTH;
+        let mut y = -0.5 - 0.5 * frame_width;
         for _ in 0..=game.height() {
             c.draw_rect(
                 Rect::from_xywh(
-                    -0.5 - 0.5 * FRAME_WIDTH,
+                    -0.5 - 0.5 * frame_width,
                     y,
-                    f32::from(game.width()) + FRAME_WIDTH,
-                    FRAME_WIDTH,
+                    f32::from(game.width()) + frame_width,
+                    frame_width,
                 ),
                 &paint,
             );
             y += 1.;
         }
 
+        // Draw the rest in screen space.
+        let display = ctx.display_data;
+        c.reset_matrix();
+
+        // Draw move numbers on settled chips.
+        if self.move_numbers {
+            // Mirroring flips the sign of the horizontal scale only.
+            let font = Font::new(Typeface::default(), LABEL_SIZE * -matrix.scale_y());
+            for ((x, y), ply) in game.move_numbers() {
+                if self.animations.iter().any(|a| a.target == (x, y)) {
+                    continue;
+                }
+
+                let text = ply.to_string();
+                let (width, bounds) = font.measure_str(&text, None);
+                let center = matrix.map_point((f32::from(x), f32::from(y)));
+                c.draw_str(
+                    &text,
+                    (
+                        center.x - width / 2.,
+                        center.y - (bounds.top + bounds.bottom) / 2.,
+                    ),
+                    &font,
+                    &Paint::new(self.colors.label(game[(x, y)]), None),
+                );
+            }
+        }
+
+        // Draw turn indicator.
+        if !self.disabled && game.result() == GameResult::Ongoing {
+            let radius = display.w.min(display.h) * INDICATOR_SIZE;
+            self.draw_chip(
+                c,
+                (display.x + 2. * radius, display.y + 2. * radius),
+                radius,
+                game.turn(),
+            );
+        }
+        // Draw game-over banner.
+        if let Some(text) = result_text(game) {
+            let font = Font::new(Typeface::default(), display.w.min(display.h) / 8.);
+            let (width, bounds) = font.measure_str(text, None);
+            let (center_x, center_y) = (display.x + display.w / 2., display.y + display.h / 2.);
+            let padding = bounds.height() / 2.;
+            c.draw_rect(
+                Rect::from_xywh(
+                    display.x,
+                    center_y - bounds.height() / 2. - padding,
+                    display.w,
+                    bounds.height() + 2. * padding,
+                ),
+                &Paint::new(BANNER, None),
+            );
+            c.draw_str(
+                text,
+                (
+                    center_x - width / 2.,
+                    center_y - (bounds.top + bounds.bottom) / 2.,
+                ),
+                &font,
+                &Paint::new(BANNER_TEXT, None),
+            );
+        }
+
         Ok(())
     }
 
@@ -324,19 +817,29 @@ struct Game(ConnectFour);
 
 impl Game {
     /// Wrapper around [`ConnectFour::create()`].
+    ///
+    /// Only supports games with regular, downward gravity.
     fn create(init_info: &GameInit) -> Result<Self> {
-        Ok(Self(ConnectFour::create(init_info)?))
+        let game = ConnectFour::create(init_info)?;
+        if !game.options().gravity_is_down() {
+            return Err(Error::new_static(
+                ErrorCode::FeatureUnsupported,
+                "only downward gravity supported\0",
+            ));
+        }
+
+        Ok(Self(game))
     }
 
     /// Wrapper around
     /// [`GameOptions::width()`](crate::game::GameOptions::width).
-    fn width(&self) -> u8 {
+    fn width(&self) -> u16 {
         self.options().width()
     }
 
     /// Wrapper around
     /// [`GameOptions::height()`](crate::game::GameOptions::height).
-    fn height(&self) -> u8 {
+    fn height(&self) -> u16 {
         self.options().height()
     }
 
@@ -354,6 +857,26 @@ impl Game {
         }
     }
 
+    /// Return iterator over all blocked cells.
+    fn blocks(&self) -> impl Iterator<Item = Pos> + '_ {
+        (0..self.height())
+            .flat_map(move |y| (0..self.width()).map(move |x| (x, y)))
+            .filter(|&p| self[p] == State::Blocked)
+    }
+
+    /// Return the cells in which a stone of the player to move would land
+    /// and immediately win.
+    ///
+    /// # Panics
+    /// Panics if the game is over.
+    fn threats(&self) -> Vec<Pos> {
+        let player = self.player_id();
+        (0..self.width())
+            .filter(|&column| self.is_winning_move(player, column))
+            .filter_map(|column| self.landing_cell(column))
+            .collect()
+    }
+
     /// Return who is currently to move.
     ///
     /// # Panics
@@ -380,13 +903,13 @@ impl DerefMut for Game {
 /// Iterator over all chips currently on the board.
 struct ChipIter<'l> {
     game: &'l ConnectFour,
-    x: u8,
-    y: u8,
+    x: u16,
+    y: u16,
 }
 
 impl<'l> Iterator for ChipIter<'l> {
     /// Has the form: `(x, y, player)`.
-    type Item = (u8, u8, bool);
+    type Item = (u16, u16, u8);
 
     fn next(&mut self) -> Option<Self::Item> {
         let width = self.game.options().width();
@@ -401,11 +924,8 @@ impl<'l> Iterator for ChipIter<'l> {
                 self.x = 0;
                 self.y += 1;
             }
-            return Some(match state {
-                State::Empty => continue,
-                State::X => (x, y, false),
-                State::O => (x, y, true),
-            });
+            let Some(player) = state.player() else { continue; };
+            return Some((x, y, player));
         }
 
         None
@@ -413,10 +933,14 @@ impl<'l> Iterator for ChipIter<'l> {
 }
 
 /// Helper for tracking mouse state.
+///
+/// Touch input is treated like a mouse as long as only one finger is down.
 #[derive(Default)]
 struct Mouse {
     current: Option<(f32, f32)>,
     clicked: Option<(f32, f32)>,
+    /// Number of fingers currently touching the screen.
+    fingers: u32,
 }
 
 impl Mouse {
@@ -446,6 +970,39 @@ impl Mouse {
         }
     }
 
+    /// Update state when a finger touches the screen.
+    ///
+    /// Only starts a click if `enabled` and no other finger is down.
+    fn finger_down(&mut self, x: f32, y: f32, enabled: bool) {
+        self.fingers = self.fingers.saturating_add(1);
+        if self.fingers != 1 {
+            // Multi-finger gestures never result in a move.
+            self.clear();
+            return;
+        }
+
+        self.update_position(x, y);
+        if enabled {
+            self.update_down();
+        }
+    }
+
+    /// Update state when a finger moves over the screen.
+    fn finger_motion(&mut self, x: f32, y: f32) {
+        if self.fingers == 1 {
+            self.update_position(x, y);
+        }
+    }
+
+    /// Update state when a finger is lifted.
+    ///
+    /// Returns the tapped location if this was a single-finger tap.
+    fn finger_up(&mut self, x: f32, y: f32) -> Option<(f32, f32)> {
+        self.fingers = self.fingers.saturating_sub(1);
+        self.update_position(x, y);
+        self.update_up()
+    }
+
     /// Clear mouse state.
     fn clear(&mut self) {
         self.clicked = None;
@@ -463,57 +1020,168 @@ struct Animation {
     /// Has the animation started already?
     started: bool,
     /// Whose chip is dropping?
-    player: bool,
+    player: u8,
+    /// Does the chip snap to its target as soon as the animation starts?
+    instant: bool,
+    /// Progress from 0 to 1 of the bounce after the chip has landed.
+    bounce: Option<f32>,
 }
 
 impl Animation {
     /// Create a new, not-started animation.
-    fn new(from: f32, to: Pos, player: bool) -> Self {
+    fn new(from: f32, to: Pos, player: u8) -> Self {
         Self {
             current: from,
             previous: None,
             target: to,
             started: false,
             player,
+            instant: false,
+            bounce: None,
         }
     }
 
     /// Update the animation state.
     ///
-    /// `max_drop` denotes the maximum height any chip could fall with this
-    /// game's configuration (use [`Game::drop_height()`]).
+    /// A chip falls `distance` cells in `speed` (use
+    /// [`AnimationMode::distance()`]).
+    /// If `speed` is zero or the drop is [`Self::instant`], the chip snaps to
+    /// its target immediately.
+    /// Otherwise, the chip moves for the time from the last update until
+    /// `now` and bounces for [`BOUNCE_TIME`] after landing.
     ///
     /// Returns true when the animation has finished.
-    fn update(&mut self, max_drop: f32) -> bool {
+    fn update(&mut self, distance: f32, speed: Duration, now: Instant) -> bool {
         if !self.started {
             return false;
         }
-        let now = Instant::now();
-        let result = if let Some(previous) = self.previous {
-            let duration = now.duration_since(previous);
-            let delta = duration.as_secs_f32() / ANIMATION_SPEED.as_secs_f32() * max_drop;
-            self.current -= delta;
-            self.current <= f32::from(self.target.1)
-        } else {
-            false
-        };
+        if speed.is_zero() || self.instant {
+            self.current = f32::from(self.target.1);
+            self.bounce = None;
+            return true;
+        }
+        let elapsed = self
+            .previous
+            .map(|previous| now.duration_since(previous).as_secs_f32() / speed.as_secs_f32());
         self.previous = Some(now);
-        result
+        let Some(elapsed) = elapsed else { return false; };
+
+        match self.bounce {
+            None => {
+                self.current -= elapsed * distance;
+                if self.current <= f32::from(self.target.1) {
+                    self.current = f32::from(self.target.1);
+                    self.bounce = Some(0.);
+                }
+                false
+            }
+            Some(ref mut progress) => {
+                *progress = (*progress + elapsed / BOUNCE_TIME).min(1.);
+                *progress >= 1.
+            }
+        }
     }
 
     /// Current position of the animated chip.
     fn position(&self) -> (f32, f32) {
-        (self.target.0.into(), self.current)
+        let offset = self.bounce.map_or(0., bounce_offset);
+        (self.target.0.into(), self.current + offset)
+    }
+}
+
+/// How the duration of a drop animation is chosen.
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub(crate) enum AnimationMode {
+    /// Every drop from the top of the board takes the animation duration,
+    /// regardless of the board height.
+    #[default]
+    ConstantDuration,
+    /// Chips fall at the same speed on every board, so drops on tall boards
+    /// take longer.
+    ConstantSpeed,
+}
+
+impl AnimationMode {
+    /// Return the distance in cells which a chip falls in the animation
+    /// duration.
+    ///
+    /// `max_drop` is the maximum height any chip could fall with this game's
+    /// configuration (use [`Game::drop_height()`]).
+    fn distance(self, max_drop: f32) -> f32 {
+        match self {
+            Self::ConstantDuration => max_drop,
+            Self::ConstantSpeed => STANDARD_DROP,
+        }
+    }
+}
+
+/// Height of a landed chip above its target after `progress` (0 to 1) of
+/// the bounce.
+///
+/// The chip hops twice with decreasing height and rests at the end.
+fn bounce_offset(progress: f32) -> f32 {
+    BOUNCE_HEIGHT * (1. - progress) * (2. * PI * progress).sin().abs()
+}
+
+/// Text describing the result of a finished game.
+fn result_text(game: &ConnectFour) -> Option<&'static str> {
+    match (game.result(), game.winner()) {
+        (GameResult::Ongoing, _) => None,
+        (_, Some(0)) => Some("X wins"),
+        (_, Some(1)) => Some("O wins"),
+        (_, Some(2)) => Some("Z wins"),
+        (_, Some(_)) => Some("Y wins"),
+        (_, None) => Some("Draw"),
+    }
+}
+
+/// Map the key with keycode `sym` to a column.
+///
+/// The keys `0` to `9` select the first ten columns and `a` to `z` the
+/// following ones.
+fn key_to_column(sym: i32) -> Option<u16> {
+    let key = char::from_u32(sym.try_into().ok()?)?;
+    if key.is_ascii_uppercase() {
+        return None;
     }
+    key.to_digit(36).map(|d| d as u16)
+}
+
+/// Should a move made with these keyboard `modifiers` skip the animation?
+///
+/// This is the case while Shift is held.
+fn instant_drop(modifiers: SDL_Keymod) -> bool {
+    modifiers & SDL_Keymod_KMOD_SHIFT != 0
+}
+
+/// Convert normalized touch coordinates into window coordinates.
+///
+/// The frontend area is assumed to extend to the bottom-right corner of the
+/// window, which is where _mirabel_ places it.
+fn touch_to_window(x: f32, y: f32, display_data: &frontend_display_data) -> (f32, f32) {
+    (
+        x * (display_data.x + display_data.w),
+        y * (display_data.y + display_data.h),
+    )
 }
 
 /// Creates a transformation matrix for easier drawing.
 ///
 /// Each cell is 1x1, the origin is in the middle of the bottom-left cell, and
 /// positive directions are up (y) and right (x).
-fn calc_matrix(game: &Game, display_data: &frontend_display_data) -> Matrix {
-    let board_width = f32::from(game.width()) + FRAME_WIDTH + 2. * MARGIN;
-    let board_height = f32::from(game.height()) + FRAME_WIDTH + 2. * MARGIN + DROP_HEIGHT;
+/// If `mirrored`, the board is flipped horizontally so that the origin is in
+/// the middle of the bottom-right cell and x grows to the left.
+/// Frame bars of `frame_width` are centered on the cell borders.
+fn calc_matrix(
+    game: &Game,
+    display_data: &frontend_display_data,
+    mirrored: bool,
+    frame_width: f32,
+) -> Matrix {
+    let board_width = f32::from(game.width()) + frame_width + 2. * MARGIN;
+    // The drop zone above the board is part of the height, so both branches
+    // below keep it visible for wide and tall windows alike.
+    let board_height = f32::from(game.height()) + frame_width + 2. * MARGIN + DROP_HEIGHT;
 
     let (scale, tx, ty);
     if board_width / board_height > display_data.w / display_data.h {
@@ -526,21 +1194,230 @@ fn calc_matrix(game: &Game, display_data: &frontend_display_data) -> Matrix {
         ty = 0.;
     }
 
-    let internal_trans = MARGIN + FRAME_WIDTH + 0.5;
+    // Only half of the outer frame bars lies outside of the cells.
+    let internal_trans = MARGIN + 0.5 * frame_width + 0.5;
     let mut matrix = Matrix::translate((display_data.x, display_data.y));
     matrix
         .pre_translate((tx, display_data.h - ty))
         .pre_scale((scale, -scale), None)
         .pre_translate((internal_trans, internal_trans));
+    if mirrored {
+        matrix
+            .pre_translate((f32::from(game.width()) - 1., 0.))
+            .pre_scale((-1., 1.), None);
+    }
     matrix
 }
 
-/// Return the chip [`Paint`] for the specified `player`.
-fn turn_to_paint(player: bool) -> Paint {
-    if player {
-        Paint::new(CHIP_O, None)
+/// The matrix from [`calc_matrix()`] and its inverse together with the
+/// inputs they were calculated from.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct CachedMatrix {
+    /// Position and size of the display area.
+    display: [f32; 4],
+    /// Width and height of the board.
+    board: (u16, u16),
+    mirrored: bool,
+    frame_width: f32,
+    matrix: Matrix,
+    inverse: Option<Matrix>,
+}
+
+impl CachedMatrix {
+    /// Return the matrices stored in `cache`.
+    ///
+    /// They are only recalculated if the display area, the board size, the
+    /// mirroring, or the frame width changed since the last call.
+    fn get(
+        cache: &mut Option<Self>,
+        game: &Game,
+        display_data: &frontend_display_data,
+        mirrored: bool,
+        frame_width: f32,
+    ) -> Self {
+        let display = [
+            display_data.x,
+            display_data.y,
+            display_data.w,
+            display_data.h,
+        ];
+        let board = (game.width(), game.height());
+        let key = (display, board, mirrored, frame_width);
+        match cache {
+            Some(c) if (c.display, c.board, c.mirrored, c.frame_width) == key => *c,
+            _ => {
+                let matrix = calc_matrix(game, display_data, mirrored, frame_width);
+                *cache.insert(Self {
+                    display,
+                    board,
+                    mirrored,
+                    frame_width,
+                    matrix,
+                    inverse: matrix.invert(),
+                })
+            }
+        }
+    }
+}
+
+/// Options of the frontend.
+///
+/// Options which are not set fall back to the defaults.
+#[derive(Clone, Copy, Default, Debug, PartialEq)]
+pub(crate) struct Options {
+    pub(crate) background: Option<Color4f>,
+    pub(crate) frame: Option<Color4f>,
+    pub(crate) chip_x: Option<Color4f>,
+    pub(crate) chip_o: Option<Color4f>,
+    /// See [`Frontend::animation_speed`].
+    pub(crate) animation_speed: Option<Duration>,
+    pub(crate) animation_mode: AnimationMode,
+    /// See [`Frontend::symbols`].
+    pub(crate) symbols: bool,
+    /// See [`Frontend::move_numbers`].
+    pub(crate) move_numbers: bool,
+    /// See [`Frontend::mirror_for_o`].
+    pub(crate) mirror_for_o: bool,
+    /// See [`Frontend::threats`].
+    pub(crate) threats: bool,
+    /// See [`Frontend::ghost_preview`].
+    pub(crate) ghost_preview: bool,
+    /// See [`Frontend::chip_radius`].
+    pub(crate) chip_radius: Option<f32>,
+    /// See [`Frontend::frame_width`].
+    pub(crate) frame_width: Option<f32>,
+}
+
+impl Options {
+    /// Resolve the chip radius (see [`clamp_chip_radius()`]).
+    fn chip_radius(&self) -> f32 {
+        self.chip_radius.map_or(CHIP_RADIUS, clamp_chip_radius)
+    }
+
+    /// Resolve the frame width (see [`clamp_frame_width()`]).
+    fn frame_width(&self) -> f32 {
+        self.frame_width.map_or(FRAME_WIDTH, clamp_frame_width)
+    }
+}
+
+/// Clamp a chip `radius` to `(0, 0.5]`.
+///
+/// Radii which are not positive fall back to the default.
+fn clamp_chip_radius(radius: f32) -> f32 {
+    if radius > 0. {
+        radius.min(CHIP_RADIUS)
+    } else {
+        CHIP_RADIUS
+    }
+}
+
+/// Clamp a frame `width` to `[0, 0.5)`.
+///
+/// An invalid width (NaN) falls back to the default.
+fn clamp_frame_width(width: f32) -> f32 {
+    if width.is_nan() {
+        FRAME_WIDTH
     } else {
-        Paint::new(CHIP_X, None)
+        width.clamp(0., MAX_FRAME_WIDTH - MAX_FRAME_WIDTH * f32::EPSILON)
+    }
+}
+
+/// Convert an opaque `rgb` color to _Skia_.
+const fn color([r, g, b]: Rgb) -> Color4f {
+    Color4f::new(r as f32 / 255., g as f32 / 255., b as f32 / 255., 1.)
+}
+
+/// Colors used for rendering.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Colors {
+    background: Color4f,
+    frame: Color4f,
+    chip_x: Color4f,
+    chip_o: Color4f,
+}
+
+impl Colors {
+    /// Resolve the colors from `options`.
+    fn new(options: &Options) -> Self {
+        Self {
+            background: options.background.unwrap_or(BACKGROUND),
+            frame: options.frame.unwrap_or(FRAME),
+            chip_x: options.chip_x.unwrap_or(CHIP_X),
+            chip_o: options.chip_o.unwrap_or(CHIP_O),
+        }
+    }
+
+    /// Return the chip [`Paint`] for the specified `player`.
+    fn chip(&self, player: u8) -> Paint {
+        Paint::new(self.chip_color(player), None)
+    }
+
+    /// Return the chip color for the specified `player`.
+    ///
+    /// The colors of a third and fourth player cannot be changed.
+    fn chip_color(&self, player: u8) -> Color4f {
+        match player {
+            0 => self.chip_x,
+            1 => self.chip_o,
+            2 => CHIP_Z,
+            _ => CHIP_Y,
+        }
+    }
+
+    /// Return a text color which contrasts with the chip of the player owning
+    /// a `state` stone.
+    fn label(&self, state: State) -> Color4f {
+        let Some(player) = state.player() else { return MARKER; };
+        let color = self.chip_color(player);
+        let luminance = 0.299 * color.r + 0.587 * color.g + 0.114 * color.b;
+        if luminance > 0.5 {
+            Color4f::new(0., 0., 0., 1.)
+        } else {
+            Color4f::new(1., 1., 1., 1.)
+        }
+    }
+}
+
+/// Widgets for changing options at runtime.
+///
+/// All labels must be NUL-terminated.
+trait OptionsUi {
+    /// Show a checkbox for `value` and return whether it was toggled.
+    fn checkbox(&mut self, label: &'static str, value: &mut bool) -> bool;
+
+    /// Show a slider for `value` from `min` to `max` and return whether it
+    /// was moved.
+    ///
+    /// Values out of this range can still be typed in.
+    fn slider(&mut self, label: &'static str, value: &mut f32, min: f32, max: f32) -> bool;
+
+    /// Show a color picker for `color` and return whether it was changed.
+    fn color(&mut self, label: &'static str, color: &mut Color4f) -> bool;
+}
+
+/// [`OptionsUi`] drawing _Dear ImGui_ widgets in the options window of
+/// _mirabel_.
+struct ImGui;
+
+impl OptionsUi for ImGui {
+    fn checkbox(&mut self, label: &'static str, value: &mut bool) -> bool {
+        // SAFETY: The label is NUL-terminated and `value` outlives the call.
+        unsafe { ImGuiThin_Checkbox(cstr(label), value) }
+    }
+
+    fn slider(&mut self, label: &'static str, value: &mut f32, min: f32, max: f32) -> bool {
+        // SAFETY: The label is NUL-terminated and `value` outlives the call.
+        unsafe { ImGuiThin_SliderFloat(cstr(label), value, min, max) }
+    }
+
+    fn color(&mut self, label: &'static str, color: &mut Color4f) -> bool {
+        let mut rgba = [color.r, color.g, color.b, color.a];
+        // SAFETY: The label is NUL-terminated and `rgba` holds the four
+        // components which are read and written.
+        let changed = unsafe { ImGuiThin_ColorEdit4(cstr(label), rgba.as_mut_ptr()) };
+        let [r, g, b, a] = rgba;
+        *color = Color4f::new(r, g, b, a);
+        changed
     }
 }
 
@@ -553,7 +1430,7 @@ fn connect_four() -> Metadata {
             minor: 1,
             patch: 0,
         },
-        features: frontend_feature_flags::default(),
+        features: frontend_feature_flags { options: true },
     }
 }
 
@@ -567,3 +1444,594 @@ fn strip_nul(s: &str) -> &str {
     s.strip_suffix('\0')
         .expect("string slice not NUL-terminated")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn colors() {
+        assert_eq!(
+            Colors {
+                background: BACKGROUND,
+                frame: FRAME,
+                chip_x: CHIP_X,
+                chip_o: CHIP_O,
+            },
+            Colors::new(&Options::default())
+        );
+
+        let blue = Color4f::new(0., 0., 1., 1.);
+        let colors = Colors::new(&Options {
+            chip_x: Some(blue),
+            ..Default::default()
+        });
+        assert_eq!(blue, colors.chip_x);
+        assert_eq!(BACKGROUND, colors.background);
+        assert_eq!(FRAME, colors.frame);
+        assert_eq!(CHIP_O, colors.chip_o);
+    }
+
+    #[test]
+    fn label_contrast() {
+        let colors = Colors::new(&Options::default());
+        assert_eq!(Color4f::new(0., 0., 0., 1.), colors.label(State::X));
+        assert_eq!(Color4f::new(1., 1., 1., 1.), colors.label(State::O));
+    }
+
+    #[test]
+    fn touch() {
+        let mut mouse = Mouse::default();
+        mouse.finger_down(1., 2., true);
+        mouse.finger_motion(1.2, 3.);
+        assert_eq!(Some((1., 2.)), mouse.finger_up(1.4, 3.));
+        assert_eq!(Some((1.4, 3.)), mouse.current);
+
+        mouse.finger_down(1., 2., false);
+        assert_eq!(None, mouse.finger_up(1., 2.));
+
+        mouse.finger_down(1., 2., true);
+        mouse.finger_down(4., 2., true);
+        mouse.finger_motion(5., 2.);
+        assert_eq!(None, mouse.finger_up(1., 2.));
+        assert_eq!(None, mouse.finger_up(4., 2.));
+        assert_eq!(0, mouse.fingers);
+    }
+
+    #[test]
+    fn cancel_click() {
+        let mut mouse = Mouse::default();
+        mouse.update_position(1., 2.);
+        mouse.update_down();
+        mouse.clear();
+        mouse.update(true);
+        assert_eq!(None, mouse.update_up());
+
+        mouse.update_down();
+        assert_eq!(Some((1., 2.)), mouse.update_up());
+    }
+
+    #[test]
+    fn calc_matrix() {
+        let game = Game::create(&GameInit::Default).unwrap();
+        let mut display = frontend_display_data {
+            ms_tick: 0,
+            fps: 0.,
+            x: 0.,
+            y: 0.,
+            w: 0.,
+            h: 0.,
+        };
+        let matrix = super::calc_matrix(&game, &display, false, FRAME_WIDTH);
+        assert_eq!(0., matrix.scale_x());
+        assert_eq!(0., matrix.scale_y());
+        assert!(matrix.invert().is_none());
+
+        display.w = 100.;
+        let matrix = super::calc_matrix(&game, &display, false, FRAME_WIDTH);
+        assert_eq!(0., matrix.scale_x());
+        assert!(!matrix.translate_x().is_nan());
+        assert!(!matrix.translate_y().is_nan());
+
+        display.h = 100.;
+        let matrix = super::calc_matrix(&game, &display, false, FRAME_WIDTH);
+        assert!(matrix.scale_x() > 0.);
+        assert!(matrix.invert().is_some());
+    }
+
+    #[test]
+    fn cached_matrix() {
+        let mut game = Game::create(&GameInit::Default).unwrap();
+        let mut display = frontend_display_data {
+            ms_tick: 0,
+            fps: 0.,
+            x: 0.,
+            y: 0.,
+            w: 700.,
+            h: 600.,
+        };
+        let mut cache = None;
+        let check = |cache: &mut Option<CachedMatrix>,
+                     game: &Game,
+                     display: &frontend_display_data,
+                     mirrored| {
+            let cached = CachedMatrix::get(cache, game, display, mirrored, FRAME_WIDTH);
+            let matrix = super::calc_matrix(game, display, mirrored, FRAME_WIDTH);
+            assert_eq!(matrix, cached.matrix);
+            assert_eq!(matrix.invert(), cached.inverse);
+            assert_eq!(Some(cached), *cache);
+        };
+        check(&mut cache, &game, &display, false);
+        check(&mut cache, &game, &display, false);
+
+        display.w = 300.;
+        check(&mut cache, &game, &display, false);
+        display.x = 20.;
+        check(&mut cache, &game, &display, false);
+        check(&mut cache, &game, &display, true);
+
+        game = Game::create(&GameInit::Standard {
+            opts: Some("9x4@4"),
+            legacy: None,
+            state: None,
+        })
+        .unwrap();
+        check(&mut cache, &game, &display, true);
+
+        display.h = 0.;
+        check(&mut cache, &game, &display, true);
+        assert!(cache.unwrap().inverse.is_none());
+    }
+
+    #[test]
+    fn frame_width() {
+        let game = Game::create(&GameInit::Default).unwrap();
+        for (w, h) in [(1000., 300.), (300., 1000.)] {
+            let display = frontend_display_data {
+                ms_tick: 0,
+                fps: 0.,
+                x: 10.,
+                y: 20.,
+                w,
+                h,
+            };
+            for frame_width in [0., FRAME_WIDTH, 0.4] {
+                let matrix = super::calc_matrix(&game, &display, false, frame_width);
+                let outer = 0.5 + 0.5 * frame_width;
+                let width = f32::from(game.width());
+                let height = f32::from(game.height());
+                let left = matrix.map_point((-outer, -outer));
+                let right =
+                    matrix.map_point((width - 1. + outer, height - 1. + outer + DROP_HEIGHT));
+
+                // The board including the drop zone is centered.
+                let epsilon = 1e-3;
+                let center_x = display.x + display.w / 2.;
+                let center_y = display.y + display.h / 2.;
+                assert!(
+                    ((left.x + right.x) / 2. - center_x).abs() < epsilon,
+                    "{frame_width}"
+                );
+                assert!(
+                    ((left.y + right.y) / 2. - center_y).abs() < epsilon,
+                    "{frame_width}"
+                );
+                // The margin is the same on both sides.
+                let margin = (left.x - display.x).min(display.y + display.h - left.y);
+                let scale = matrix.scale_x();
+                assert!((margin - MARGIN * scale).abs() < epsilon, "{frame_width}");
+            }
+        }
+    }
+
+    #[test]
+    fn chip_options() {
+        let options = |chip_radius, frame_width| Options {
+            chip_radius,
+            frame_width,
+            ..Default::default()
+        };
+        assert_eq!(CHIP_RADIUS, options(None, None).chip_radius());
+        assert_eq!(FRAME_WIDTH, options(None, None).frame_width());
+        assert_eq!(0.3, options(Some(0.3), Some(0.2)).chip_radius());
+        assert_eq!(0.2, options(Some(0.3), Some(0.2)).frame_width());
+        assert_eq!(CHIP_RADIUS, options(Some(0.8), Some(0.8)).chip_radius());
+        assert!(options(Some(0.8), Some(0.8)).frame_width() < MAX_FRAME_WIDTH);
+        assert_eq!(CHIP_RADIUS, options(Some(0.), Some(-1.)).chip_radius());
+        assert_eq!(0., options(Some(0.), Some(-1.)).frame_width());
+        assert_eq!(CHIP_RADIUS, options(Some(f32::NAN), None).chip_radius());
+        assert_eq!(FRAME_WIDTH, options(None, Some(f32::NAN)).frame_width());
+
+        let frontend = Frontend::create(Some(&options(Some(0.4), Some(0.3)))).unwrap();
+        assert_eq!(0.4, frontend.chip_radius);
+        assert_eq!(0.3, frontend.frame_width);
+    }
+
+    /// [`OptionsUi`] which records the labels and changes some values.
+    #[derive(Default)]
+    struct FakeUi {
+        labels: Vec<&'static str>,
+        checks: Vec<&'static str>,
+        slides: Vec<(&'static str, f32)>,
+        colors: Vec<(&'static str, Color4f)>,
+    }
+
+    impl OptionsUi for FakeUi {
+        fn checkbox(&mut self, label: &'static str, value: &mut bool) -> bool {
+            let label = self.record(label);
+            let toggled = self.checks.contains(&label);
+            *value ^= toggled;
+            toggled
+        }
+
+        fn slider(&mut self, label: &'static str, value: &mut f32, min: f32, max: f32) -> bool {
+            assert!(min <= *value && *value <= max, "{label}");
+            let label = self.record(label);
+            let slide = self.slides.iter().find(|(l, _)| *l == label);
+            slide.map(|&(_, v)| *value = v).is_some()
+        }
+
+        fn color(&mut self, label: &'static str, color: &mut Color4f) -> bool {
+            let label = self.record(label);
+            let change = self.colors.iter().find(|(l, _)| *l == label);
+            change.map(|&(_, c)| *color = c).is_some()
+        }
+    }
+
+    impl FakeUi {
+        /// Record `label` and return the static label without the NUL.
+        fn record(&mut self, label: &'static str) -> &'static str {
+            let label = label.strip_suffix('\0').expect("label without NUL");
+            assert!(!self.labels.contains(&label), "duplicate {label}");
+            self.labels.push(label);
+            label
+        }
+    }
+
+    #[test]
+    fn runtime_options() {
+        let mut frontend = Frontend::create(None).unwrap();
+        let mut ui = FakeUi::default();
+        frontend.edit_options(&mut ui);
+        assert!(ui.labels.contains(&"Background"));
+        assert!(ui.labels.contains(&"Frame width"));
+        assert_eq!(Colors::new(&Options::default()), frontend.colors);
+        assert!(!frontend.symbols);
+        assert_eq!(ANIMATION_SPEED, frontend.animation_speed);
+
+        let blue = Color4f::new(0., 0., 1., 1.);
+        let mut ui = FakeUi {
+            checks: vec!["Symbols on chips", "Mark threats", "Constant falling speed"],
+            slides: vec![
+                ("Chip radius", 0.3),
+                ("Frame width", 0.8),
+                ("Animation duration (s)", 0.),
+            ],
+            colors: vec![("Chip O", blue)],
+            ..Default::default()
+        };
+        frontend.edit_options(&mut ui);
+        assert!(frontend.symbols && frontend.threats);
+        assert!(!frontend.move_numbers);
+        assert_eq!(AnimationMode::ConstantSpeed, frontend.animation_mode);
+        // Zero makes chips snap into place.
+        assert_eq!(Duration::ZERO, frontend.animation_speed);
+        assert_eq!(blue, frontend.colors.chip_o);
+        assert_eq!(CHIP_X, frontend.colors.chip_x);
+        assert_eq!(0.3, frontend.chip_radius);
+        // Typed-in values are clamped like the options.
+        assert!(frontend.frame_width < MAX_FRAME_WIDTH);
+
+        let mut ui = FakeUi {
+            checks: vec!["Constant falling speed"],
+            slides: vec![
+                ("Chip radius", -1.),
+                ("Frame width", -1.),
+                ("Animation duration (s)", 0.25),
+            ],
+            ..Default::default()
+        };
+        frontend.edit_options(&mut ui);
+        assert_eq!(CHIP_RADIUS, frontend.chip_radius);
+        assert_eq!(0., frontend.frame_width);
+        assert_eq!(AnimationMode::ConstantDuration, frontend.animation_mode);
+        assert_eq!(Duration::from_millis(250), frontend.animation_speed);
+    }
+
+    #[test]
+    fn threats() {
+        let create = |state| {
+            Game::create(&GameInit::Standard {
+                opts: None,
+                legacy: None,
+                state: Some(state),
+            })
+            .unwrap()
+        };
+
+        // X threatens to complete the bottom row on both sides.
+        assert_eq!(vec![(0, 0), (4, 0)], create("/X/X/X//O/OO#x").threats());
+        // O could block only one of them.
+        assert_eq!(vec![] as Vec<Pos>, create("/X/X/X//O/OO#o").threats());
+        assert_eq!(vec![(5, 3)], create("/X/X/X//OOO/O#o").threats());
+        assert_eq!(vec![] as Vec<Pos>, create("#x").threats());
+    }
+
+    #[test]
+    fn mirrored_columns() {
+        let mut frontend = Frontend::create(Some(&Options {
+            mirror_for_o: true,
+            ..Default::default()
+        }))
+        .unwrap();
+        let mut game = Game::create(&GameInit::Default).unwrap();
+        let display = frontend_display_data {
+            ms_tick: 0,
+            fps: 0.,
+            x: 10.,
+            y: 20.,
+            w: 700.,
+            h: 600.,
+        };
+        assert!(!frontend.mirrored(&game));
+        game.make_move(1, MoveDataSync::with_default(&0)).unwrap();
+        assert!(frontend.mirrored(&game));
+
+        let plain = super::calc_matrix(&game, &display, false, FRAME_WIDTH);
+        let mirrored = super::calc_matrix(&game, &display, true, FRAME_WIDTH);
+        let inverse = mirrored.invert().unwrap();
+        frontend.game = Some(Game::create(&GameInit::Default).unwrap());
+        for column in 0..game.width() {
+            let logical = (f32::from(column), 2.);
+            // The mirrored column is shown where the opposite one would be.
+            let opposite = (f32::from(game.width() - 1 - column), 2.);
+            let point = mirrored.map_point(logical);
+            assert!((point.x - plain.map_point(opposite).x).abs() < 1e-3);
+            assert!((point.y - plain.map_point(opposite).y).abs() < 1e-3);
+
+            let x = inverse.map_point(point).x;
+            assert_eq!(Some(column), frontend.column_at(x));
+        }
+
+        frontend.mirror_for_o = false;
+        assert!(!frontend.mirrored(&game));
+    }
+
+    #[test]
+    fn guide() {
+        let mut frontend = Frontend::create(None).unwrap();
+        frontend.game = Some(
+            Game::create(&GameInit::Standard {
+                opts: None,
+                legacy: None,
+                state: Some("/XOXOXO#x"),
+            })
+            .unwrap(),
+        );
+        assert_eq!(None, frontend.guide());
+
+        frontend.mouse.update_position(2.4, 1.);
+        assert_eq!(Some(2), frontend.guide());
+        frontend.mouse.update_position(2.6, 1.);
+        assert_eq!(Some(3), frontend.guide());
+        // The column under the pointer is full.
+        frontend.mouse.update_position(0.6, 1.);
+        assert_eq!(None, frontend.guide());
+        frontend.mouse.update_position(6.4, 1.);
+        assert_eq!(Some(6), frontend.guide());
+        frontend.mouse.update_position(6.6, 1.);
+        assert_eq!(None, frontend.guide());
+
+        frontend.mouse.update_position(3., 1.);
+        frontend.disabled = true;
+        assert_eq!(None, frontend.guide());
+        frontend.disabled = false;
+        frontend
+            .animations
+            .push_back(Animation::new(5.2, (0, 2), 0));
+        assert_eq!(None, frontend.guide());
+    }
+
+    #[test]
+    fn ghost_preview() {
+        let mut frontend = Frontend::create(Some(&Options {
+            ghost_preview: true,
+            ..Default::default()
+        }))
+        .unwrap();
+        frontend.game = Some(Game::create(&GameInit::Default).unwrap());
+        frontend.mouse.update_position(2., 1.);
+        assert_eq!(Some(2), frontend.preview());
+        assert_eq!(None, frontend.ghost());
+
+        frontend.disabled = true;
+        assert_eq!(None, frontend.preview());
+        assert_eq!(None, frontend.hovered());
+        assert_eq!(Some(2), frontend.ghost());
+        frontend.mouse.update_position(9., 1.);
+        assert_eq!(None, frontend.ghost());
+
+        frontend.mouse.update_position(2., 1.);
+        frontend.ghost_preview = false;
+        assert_eq!(None, frontend.ghost());
+    }
+
+    #[test]
+    fn drop_zone_visible() {
+        for options in ["7x6@4", "1x20@1", "20x1@1"] {
+            let game = Game::create(&GameInit::Standard {
+                opts: Some(options),
+                legacy: None,
+                state: None,
+            })
+            .unwrap();
+            for (w, h) in [(1000., 10.), (10., 1000.), (300., 300.), (1., 10000.)] {
+                let display = frontend_display_data {
+                    ms_tick: 0,
+                    fps: 0.,
+                    x: 50.,
+                    y: 20.,
+                    w,
+                    h,
+                };
+                let matrix = super::calc_matrix(&game, &display, false, FRAME_WIDTH);
+                // Top-left and top-right of the highest dropping chip.
+                let top = game.drop_height() + 0.5;
+                for x in [-0.5, f32::from(game.width()) - 0.5] {
+                    let point = matrix.map_point((x, top));
+                    let epsilon = 1e-3 * w.max(h);
+                    assert!(point.y >= display.y - epsilon, "{options} {w}x{h}");
+                    assert!(point.y <= display.y + h + epsilon, "{options} {w}x{h}");
+                    assert!(point.x >= display.x - epsilon, "{options} {w}x{h}");
+                    assert!(point.x <= display.x + w + epsilon, "{options} {w}x{h}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn restarts() {
+        let create = |options, state| {
+            Game::create(&GameInit::Standard {
+                opts: Some(options),
+                legacy: None,
+                state: Some(state),
+            })
+            .unwrap()
+        };
+        let r = RESTART_KEY as i32;
+
+        let mut frontend = Frontend::create(None).unwrap();
+        assert!(!frontend.restarts(r));
+        // Running games are never restarted.
+        frontend.game = Some(create("7x6@4", "#x"));
+        assert!(!frontend.restarts(r));
+        frontend.game = Some(create("7x6@4", "xxxx/ooo#X"));
+        assert!(frontend.restarts(r));
+        assert!(!frontend.restarts('R' as i32));
+        assert!(!frontend.restarts('3' as i32));
+        frontend.game = Some(create("2x1@2", "x/o#-"));
+        assert!(frontend.restarts(r));
+
+        // The key selects a column on wide boards while the game is running.
+        frontend.game = Some(create("30x2@4", "#x"));
+        assert!(!frontend.restarts(r));
+        frontend.game = Some(create("30x2@4", "x/x/x/x#X"));
+        assert!(frontend.restarts(r));
+    }
+
+    #[test]
+    fn key_to_column() {
+        assert_eq!(Some(0), super::key_to_column('0' as i32));
+        assert_eq!(Some(9), super::key_to_column('9' as i32));
+        assert_eq!(Some(10), super::key_to_column('a' as i32));
+        assert_eq!(Some(35), super::key_to_column('z' as i32));
+        assert_eq!(None, super::key_to_column('A' as i32));
+        assert_eq!(None, super::key_to_column(' ' as i32));
+        assert_eq!(None, super::key_to_column(-1));
+    }
+
+    #[test]
+    fn animation() {
+        let start = Instant::now();
+        let at = |micros| start + Duration::from_micros(micros);
+
+        let speed = Duration::from_micros(1);
+        let mut animation = Animation::new(5.2, (0, 2), 0);
+        assert!(!animation.update(5.2, speed, at(0)));
+        animation.started = true;
+        assert!(!animation.update(5.2, speed, at(0)));
+        // Landing starts the bounce.
+        assert!(!animation.update(5.2, speed, at(1000)));
+        assert_eq!(Some(0.), animation.bounce);
+        assert!(animation.update(5.2, speed, at(2000)));
+        assert_eq!((0., 2.), animation.position());
+
+        let speed = Duration::from_millis(20);
+        let mut animation = Animation::new(5.2, (0, 2), 0);
+        animation.started = true;
+        let mut lowest = f32::MAX;
+        let mut finished = false;
+        for i in 0..1000 {
+            finished = animation.update(5.2, speed, at(100 * i));
+            lowest = lowest.min(animation.position().1);
+            if finished {
+                break;
+            }
+        }
+        assert!(finished);
+        assert_eq!((0., 2.), animation.position());
+        // The chip never sinks below its target.
+        assert_eq!(2., lowest);
+
+        let mut animation = Animation::new(5.2, (0, 2), 0);
+        animation.started = true;
+        assert!(animation.update(5.2, Duration::ZERO, at(0)));
+        assert_eq!((0., 2.), animation.position());
+
+        let speed = Duration::from_secs(1);
+        let mut animation = Animation::new(5.2, (0, 2), 0);
+        animation.instant = true;
+        assert!(!animation.update(5.2, speed, at(0)));
+        animation.started = true;
+        assert!(animation.update(5.2, speed, at(0)));
+        assert_eq!((0., 2.), animation.position());
+
+        assert_eq!(0., bounce_offset(0.));
+        assert!(bounce_offset(0.25) > bounce_offset(0.75));
+        assert!(bounce_offset(0.25) <= BOUNCE_HEIGHT);
+        assert!(bounce_offset(1.).abs() < f32::EPSILON);
+
+        assert!(instant_drop(SDL_Keymod_KMOD_SHIFT));
+        assert!(!instant_drop(0));
+    }
+
+    /// Let one `speed` pass for `animation` and update it.
+    fn step(animation: &mut Animation, distance: f32, speed: Duration) -> bool {
+        let previous = *animation.previous.get_or_insert_with(Instant::now);
+        animation.update(distance, speed, previous + speed)
+    }
+
+    #[test]
+    fn animation_mode_duration() {
+        let speed = Duration::from_millis(100);
+        let max_drop = 19. + DROP_HEIGHT;
+        let distance = AnimationMode::ConstantDuration.distance(max_drop);
+        assert_eq!(max_drop, distance);
+
+        let mut animation = Animation::new(max_drop, (0, 0), 0);
+        animation.started = true;
+        // A full-height drop lands within one duration.
+        assert!(!step(&mut animation, distance, speed));
+        assert_eq!(Some(0.), animation.bounce);
+        assert!(step(&mut animation, distance, speed));
+        assert_eq!((0., 0.), animation.position());
+    }
+
+    #[test]
+    fn animation_mode_speed() {
+        let speed = Duration::from_millis(100);
+        let max_drop = 19. + DROP_HEIGHT;
+        let distance = AnimationMode::ConstantSpeed.distance(max_drop);
+        assert_eq!(STANDARD_DROP, distance);
+        assert_eq!(
+            distance,
+            AnimationMode::ConstantSpeed.distance(5. + DROP_HEIGHT)
+        );
+
+        let mut animation = Animation::new(max_drop, (0, 0), 0);
+        animation.started = true;
+        // The chip still falls after the duration on a tall board.
+        assert!(!step(&mut animation, distance, speed));
+        assert_eq!(None, animation.bounce);
+        assert!((animation.current - (max_drop - STANDARD_DROP)).abs() < 0.1);
+        let mut steps = 1;
+        while !step(&mut animation, distance, speed) {
+            steps += 1;
+            assert!(steps < 10);
+        }
+        // The chip lands on the fourth step and bounces on the fifth.
+        assert_eq!(4, steps);
+        assert_eq!((0., 0.), animation.position());
+    }
+}
@@ -1,10 +1,12 @@
 //! _Connect Four_ game for _surena_.
 
 use std::fmt::{self, Display, Write};
+use std::iter;
+use std::num::IntErrorKind;
 use std::ops::Index;
 use std::str::FromStr;
 
-use crate::bitvec::BitVec;
+use crate::bitvec::{BitVec, Board};
 use mirabel::game::{GameFeatures, MoveCode};
 use mirabel::{
     cstr,
@@ -18,20 +20,49 @@ use mirabel::{
     plugin_get_game_methods,
 };
 use mirabel::{MoveDataSync, ValidCString};
+#[cfg(feature = "serde")]
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 
 pub const GAME_NAME: &str = "Connect_Four\0";
 pub const VARIANT_NAME: &str = "Classic\0";
+pub const POP_OUT_VARIANT_NAME: &str = "Pop_Out\0";
 pub const IMPL_NAME: &str = "2-bitviktor\0";
 
-const DEFAULT_WIDTH: u8 = 7;
-const DEFAULT_HEIGHT: u8 = 6;
-const DEFAULT_LENGTH: u8 = 4;
+const DEFAULT_WIDTH: u16 = 7;
+const DEFAULT_HEIGHT: u16 = 6;
+const DEFAULT_LENGTH: u16 = 4;
+/// Fixed seed of the Zobrist numbers so that ids are stable across runs.
+const ZOBRIST_SEED: u64 = 0x2b17_c04e_c7f0_0e4a;
+/// Fixed seed for [`ConnectFour::get_move_hash()`].
+#[cfg(test)]
+const MOVE_HASH_SEED: u64 = 0x5f3c_9a81_d26e_47b3;
+/// Version of the format of [`ConnectFour::export_serialized()`].
+const SERIALIZED_VERSION: u8 = 1;
+/// Flag in a [`move_code`] marking a pop instead of a drop.
+const POP_FLAG: move_code = 1 << (move_code::BITS - 1);
+/// Score of a decided game in [`ConnectFour::evaluate`].
+#[cfg(test)]
+const WIN_SCORE: i32 = 1_000_000;
+/// Maximum value of [`GameOptions::players`].
+const MAX_PLAYERS: u8 = 4;
+/// Stone characters of the players in turn order.
+const PLAYER_CHARS: [char; MAX_PLAYERS as usize] = ['X', 'O', 'Z', 'Y'];
 
 /// Generate [`Metadata`] struct.
 fn connect_four() -> Metadata {
+    metadata(VARIANT_NAME)
+}
+
+/// Generate [`Metadata`] struct for the _Pop Out_ variant.
+fn pop_out() -> Metadata {
+    metadata(POP_OUT_VARIANT_NAME)
+}
+
+/// Generate [`Metadata`] struct for the variant `variant_name`.
+fn metadata(variant_name: &str) -> Metadata {
     Metadata {
         game_name: cstr(GAME_NAME),
-        variant_name: cstr(VARIANT_NAME),
+        variant_name: cstr(variant_name),
         impl_name: cstr(IMPL_NAME),
         version: semver {
             major: 0,
@@ -45,100 +76,111 @@ fn connect_four() -> Metadata {
     }
 }
 
-plugin_get_game_methods!(ConnectFour{connect_four()});
+plugin_get_game_methods!(ConnectFour{connect_four()}, PopOut{pop_out()});
 
 /// Struct holding options and game state.
 #[derive(PartialEq, Eq, Clone, Debug)]
 pub(crate) struct ConnectFour {
     options: GameOptions,
+    /// Numbers for [`Self::id()`], which only depend on the options.
+    zobrist: Zobrist,
+    /// Edge masks for [`Self::has_line()`], which only depend on the options.
+    masks: LineMasks,
     data: GameData,
 }
 
 impl ConnectFour {
-    /// Convert between [`Pos`] and [`BitVec`] index.
-    fn idx(&self, pos: Pos) -> usize {
-        2 * (usize::from(pos.0) * usize::from(self.options.height) + usize::from(pos.1))
-    }
-
-    /// Set `state` at `pos` of game board.
-    fn set(&mut self, pos: Pos, state: State) {
-        let index = self.idx(pos);
-        if let State::Empty = state {
-            self.data.board.set(index, false);
-        } else {
-            self.data.board.set(index, true);
-
-            match state {
-                State::X => self.data.board.set(index + 1, false),
-                State::O => self.data.board.set(index + 1, true),
-                _ => unreachable!(),
-            }
+    /// Create a new game with an empty board.
+    fn with_options(options: GameOptions) -> Self {
+        Self {
+            options,
+            zobrist: Zobrist::new(&options),
+            masks: LineMasks::new(&options),
+            data: GameData::new(&options),
         }
     }
 
-    /// Iterate through the fields of the game board starting at `pos` and
-    /// going in `direction`.
-    fn iter(&self, pos: Pos, direction: Direction) -> DirectionIter {
-        DirectionIter {
-            game: self,
-            current: Some(pos),
-            direction,
+    /// Create a new game and drop stones into the `moves` columns, taking turns
+    /// between the players.
+    ///
+    /// The handicap stones (see [`Self::place_handicap()`]) are placed first.
+    ///
+    /// # Errors
+    /// Fails with [`InvalidInput`] if a move is illegal or the game ends
+    /// before all moves are played.
+    #[cfg(test)]
+    pub(crate) fn replay(options: GameOptions, moves: &[u8]) -> Result<Self> {
+        let mut game = Self::with_options(options);
+        game.place_handicap()?;
+        for (i, &column) in moves.iter().enumerate() {
+            if game.data.result.is_over() {
+                return Err(Error::new_dynamic(
+                    InvalidInput,
+                    format!("game ended after {i} of {} moves", moves.len()),
+                ));
+            }
+            let player = player_to_id(game.data.turn);
+            let code = move_code::from(column);
+            game.is_legal_move(player, MoveDataSync::with_default(&code))?;
+            game.make_move(player, MoveDataSync::with_default(&code))?;
         }
-    }
 
-    /// Provide read-only access to the internal options.
-    #[cfg(feature = "mirabel")]
-    pub(crate) fn options(&self) -> &GameOptions {
-        &self.options
+        Ok(game)
     }
 
-    /// Return who is currently to move.
+    /// Serialize options and state into `buf`.
     ///
-    /// # Panics
-    /// Panics if the game is over.
-    #[cfg(feature = "mirabel")]
-    pub(crate) fn turn(&self) -> bool {
-        assert!(
-            matches!(self.data.result, GameResult::Ongoing),
-            "game is already over"
-        );
-        self.data.turn
-    }
-
-    /// Check if a move can be performed in this column.
+    /// The format is as follows:
+    /// 1. One byte with the format version [`SERIALIZED_VERSION`].
+    ///    Everything after it may change in future versions.
+    /// 2. Width, height, length, and the number of handicap stones as two
+    ///    little-endian bytes each and one byte of option flags (see
+    ///    [`GameOptions::flags()`]).
+    /// 3. The bits of [`GameData::board`] packed by [`BitVec::to_bytes()`].
+    /// 4. One byte with the low bit of [`GameData::turn`] as the least
+    ///    significant bit, the result in the next two bits (`0` → ongoing,
+    ///    `1` → winner, and `2` → draw), the high bit of the turn after
+    ///    them, and [`GameOptions::first_player`] in the two bits after that.
     ///
-    /// # Panics
-    /// Panics if the column id is invalid.
-    #[cfg(feature = "mirabel")]
-    pub(crate) fn possible_move(&self, column: u8) -> bool {
-        !self.data.result.is_over()
-            && matches!(self[(column, self.options.height - 1)], State::Empty)
-    }
-
-    /// Return the row number of the lowest free cell in this `column`.
+    /// The handicap is stored although its stones are part of the board
+    /// already since `Self::restart()` places them again.
     ///
-    /// # Panics
-    /// Panics if there is no such free cell.
-    pub(crate) fn free_cell(&self, column: u8) -> u8 {
-        self.iter((column, 0), Direction::N)
-            .enumerate()
-            .find(|&(_, s)| s == State::Empty)
-            .expect("move impossible")
-            .0
-            .try_into()
-            .unwrap()
-    }
-}
+    /// This is for internal use only since the _surena_ bindings offer no
+    /// hook for serializing games.
+    /// Games are created from such bytes by [`GameMethods::create()`] with
+    /// [`GameInit::Serialized`].
+    #[allow(dead_code)]
+    pub(crate) fn export_serialized(&self, buf: &mut Vec<u8>) {
+        buf.push(SERIALIZED_VERSION);
+        let options = &self.options;
+        for number in [
+            options.width,
+            options.height,
+            options.length,
+            options.handicap,
+        ] {
+            buf.extend(number.to_le_bytes());
+        }
+        buf.push(options.flags());
 
-impl GameMethods for ConnectFour {
-    type Move = MoveCode;
+        buf.extend(self.data.board.to_bytes());
 
-    /// Creates a new instance of the game.
+        let result = match self.data.result {
+            GameResult::Ongoing => 0,
+            GameResult::Winner => 1,
+            GameResult::Draw => 2,
+        };
+        let turn = self.data.turn;
+        let first = self.options.first_player;
+        buf.push(turn & 1 | result << 1 | (turn >> 1) << 3 | first << 4);
+    }
+
+    /// Same as [`GameMethods::create()`] but enables popping for the
+    /// _Pop Out_ variant.
     ///
-    /// See [`GameOptions::new()`] for a documentation of the options string.
-    /// See [`Self::import_state()`] for a documentation of the state string.
-    /// Serialized `init_info` is not supported.
-    fn create(init_info: &GameInit) -> Result<Self> {
+    /// The variant is set before the state is imported, so that the state is
+    /// validated under its rules.
+    fn create_variant(init_info: &GameInit, pop_out: bool) -> Result<Self> {
         let (options, state) = match *init_info {
             GameInit::Default => (None, None),
             GameInit::Standard {
@@ -154,885 +196,5359 @@ impl GameMethods for ConnectFour {
                 }
                 (opts, state)
             }
-            GameInit::Serialized(_) => {
-                return Err(Error::new_static(
-                    ErrorCode::FeatureUnsupported,
-                    "serialized init info unsupported\0",
-                ))
+            GameInit::Serialized(bytes) => {
+                let mut game = Self::import_serialized(bytes)?;
+                game.options.pop_out = pop_out;
+                return Ok(game);
             }
         };
 
-        let options = options
-            .map(GameOptions::new)
-            .transpose()?
-            .unwrap_or_default();
-        let mut game = Self {
-            options,
-            data: GameData::new(&options),
+        let options = GameOptions {
+            pop_out,
+            ..options
+                .map(GameOptions::new)
+                .transpose()?
+                .unwrap_or_default()
         };
+        let mut game = Self::with_options(options);
         game.import_state(state)?;
 
         Ok(game)
     }
 
-    fn export_options(&mut self, _player: player_id, str_buf: &mut ValidCString) -> Result<()> {
-        write!(
-            str_buf,
-            "{} {} {}",
-            self.options.width, self.options.height, self.options.length
-        )
-        .expect("writing options buffer failed");
-
-        Ok(())
-    }
-
-    fn copy_from(&mut self, other: &mut Self) -> Result<()> {
-        debug_assert_eq!(self.options, other.options, "options mismatch in copy_from");
-        self.data.copy_from(&other.data);
-
-        Ok(())
-    }
-
-    /// Imports state in the following format:
-    ///
-    /// ```text
-    /// XOOXXXO/XOOX//OXXO#x
-    /// ```
-    ///
-    /// Each sequence of `X`s and `O`s between `/`s represents a column of
-    /// stones from bottom to top.
-    /// A hashtag-separated, lower-case letter at the end indicates who plays
-    /// next.
-    /// An upper-case letter indicates that this player has won.
-    /// A dash indicates a draw.
-    ///
-    /// The state is not required to have a plausible ratio between `X`s and
-    /// `O`s and the winning player is not required to actually have a large
-    /// enough streak.
-    fn import_state(&mut self, string: Option<&str>) -> Result<()> {
-        self.data.reset();
-        let mut string = match string {
-            Some(s) => s.trim_start().chars(),
-            None => {
-                return Ok(());
+    /// Create a game from `bytes` produced by [`Self::export_serialized()`].
+    fn import_serialized(bytes: &[u8]) -> Result<Self> {
+        match bytes.first() {
+            Some(&SERIALIZED_VERSION) => (),
+            Some(_) => {
+                return Err(Error::new_static(
+                    InvalidInput,
+                    "unsupported serialization version\0",
+                ))
             }
+            None => return Err(Error::new_static(InvalidInput, "serialized game empty\0")),
+        }
+        let [_, w0, w1, h0, h1, l0, l1, c0, c1, flags, ref rest @ ..] = *bytes else {
+            return Err(Error::new_static(
+                InvalidInput,
+                "serialized options truncated\0",
+            ));
         };
+        let options = GameOptions {
+            width: u16::from_le_bytes([w0, w1]),
+            height: u16::from_le_bytes([h0, h1]),
+            length: u16::from_le_bytes([l0, l1]),
+            handicap: u16::from_le_bytes([c0, c1]),
+            ..Default::default()
+        }
+        .with_flags(flags);
 
-        let mut pos = (0, 0);
-        for character in &mut string {
-            if character == '#' {
-                break;
+        let Some((&flags, board)) = rest.split_last() else {
+            return Err(Error::new_static(
+                InvalidInput,
+                "serialized state truncated\0",
+            ));
+        };
+        let options = GameOptions {
+            first_player: flags >> 4 & 0b11,
+            ..options
+        }
+        .validated()?;
+        if flags >> 6 != 0 {
+            return Err(Error::new_static(
+                InvalidInput,
+                "invalid flags in serialized state\0",
+            ));
+        }
+        let result = match flags >> 1 & 0b11 {
+            0 => GameResult::Ongoing,
+            1 => GameResult::Winner,
+            2 => GameResult::Draw,
+            _ => {
+                return Err(Error::new_static(
+                    InvalidInput,
+                    "invalid result in serialized state\0",
+                ))
             }
+        };
 
-            if character == '/' {
-                pos.0 += 1;
-                pos.1 = 0;
-                if pos.0 >= self.options.width {
+        let turn = flags & 1 | (flags >> 2 & 0b10);
+        Self::from_parts(options, board, turn, result)
+    }
+
+    /// Create a game from its `options`, the `board` packed by
+    /// [`BitVec::to_bytes()`], and the rest of the state.
+    ///
+    /// # Errors
+    /// Fails if the board does not fit the options, has floating stones, or
+    /// the player to move does not exist.
+    fn from_parts(
+        options: GameOptions,
+        board: &[u8],
+        turn: u8,
+        result: GameResult,
+    ) -> Result<Self> {
+        if turn >= options.players {
+            return Err(Error::new_static(
+                InvalidInput,
+                "player to move does not exist\0",
+            ));
+        }
+        let mut game = Self::with_options(options);
+
+        let size = game.data.board.len();
+        let board = BitVec::from_bytes(board, size)?;
+        let cell_bits = options.cell_bits();
+
+        for lane in 0..game.lanes() {
+            let mut floating = false;
+            for depth in 0..game.lane_depth() {
+                let pos = game.lane_cell(lane, depth);
+                let index = game.idx(pos);
+                let bits = (0..cell_bits).fold(0, |n, i| n | usize::from(board[index + i]) << i);
+                let Some(state) = CELL_STATES[bits] else {
+                    return Err(Error::new_static(
+                        InvalidInput,
+                        "invalid cell in serialized board\0",
+                    ));
+                };
+                if state == State::Empty {
+                    floating = true;
+                } else if state == State::Blocked {
+                    // Stones may rest on blocked cells.
+                    floating = false;
+                } else if floating {
                     return Err(Error::new_static(
                         InvalidInput,
-                        "state has too many columns\0",
+                        "floating stone in serialized board\0",
                     ));
+                } else {
+                    game.data.moves = game.data.moves.wrapping_add(1);
                 }
-                continue;
-            }
-
-            if pos.1 >= self.options.height {
-                return Err(Error::new_static(InvalidInput, "state has too many rows\0"));
+                game.set(pos, state);
             }
-
-            self.set(
-                pos,
-                if character.eq_ignore_ascii_case(&'X') {
-                    State::X
-                } else if character.eq_ignore_ascii_case(&'O') {
-                    State::O
-                } else {
-                    return Err(player_string_error(character));
-                },
-            );
-
-            pos.1 += 1;
-        }
-
-        let player = string.as_str().trim();
-        if player.eq_ignore_ascii_case("X") {
-            self.data.turn = false;
-        } else if player.eq_ignore_ascii_case("O") {
-            self.data.turn = true;
-        } else if player == "-" {
-            self.data.result = GameResult::Draw;
-        } else {
-            return Err(player_string_error(player));
         }
 
-        // '-' is not uppercase.
-        if player.chars().all(char::is_uppercase) {
-            self.data.result = GameResult::Winner;
-        }
+        game.data.turn = turn;
+        game.data.result = result;
+        game.debug_assert_heights();
 
-        Ok(())
+        Ok(game)
     }
 
-    fn export_state(&mut self, _player: player_id, str_buf: &mut ValidCString) -> Result<()> {
-        const ERROR: &str = "writing state buffer failed";
+    /// Draw the board with column numbers below it.
+    ///
+    /// Shared by [`GameMethods::print()`] and [`Display`].
+    fn write_board(&self, w: &mut impl Write) -> fmt::Result {
+        let col_chars = self.options.col_chars();
 
-        for x in 0..self.options.width {
-            if x != 0 {
-                write!(str_buf, "/").expect(ERROR);
+        for y in (0..self.options.height).rev() {
+            for state in self.iter((0, y), Direction::E) {
+                write!(w, "|{state:col_chars$}")?;
             }
-            for y in self.iter((x, 0), Direction::N) {
-                write!(
-                    str_buf,
-                    "{}",
-                    match y {
-                        State::X => 'X',
-                        State::O => 'O',
-                        _ => break,
-                    }
-                )
-                .expect(ERROR);
+            write!(w, "|")?;
+            if let Direction::W | Direction::E = self.options.gravity {
+                // Sideways gravity: stones are dropped into rows.
+                write!(w, " {}", self.options.column_label(y))?;
             }
+            writeln!(w)?;
         }
-        write!(str_buf, "#").expect(ERROR);
-
-        write!(
-            str_buf,
-            "{}",
-            match (self.data.turn, self.data.result) {
-                (false, GameResult::Ongoing) => 'x',
-                (true, GameResult::Ongoing) => 'o',
-                (false, GameResult::Winner) => 'X',
-                (true, GameResult::Winner) => 'O',
-                (_, GameResult::Draw) => '-',
-            }
-        )
-        .expect(ERROR);
-
-        Ok(())
+        for x in 0..self.options.width {
+            write!(w, " {:>col_chars$}", self.options.column_label(x))?;
+        }
+        writeln!(w, " ")
     }
 
-    fn player_count(&mut self) -> Result<u8> {
-        Ok(2)
+    /// Convert between [`Pos`] and [`BitVec`] index.
+    fn idx(&self, pos: Pos) -> usize {
+        self.options.cell_bits()
+            * (usize::from(pos.0) * usize::from(self.options.height) + usize::from(pos.1))
     }
 
-    fn players_to_move(&mut self, players: &mut Vec<player_id>) -> Result<()> {
-        if !self.data.result.is_over() {
-            players.push(player_to_id(self.data.turn));
+    /// Set `state` at `pos` of game board.
+    fn set(&mut self, pos: Pos, state: State) {
+        let index = self.stone_idx(pos);
+        self.data.hash ^= self.zobrist.cell(index, self[pos]) ^ self.zobrist.cell(index, state);
+        let index = self.idx(pos);
+        let bits = state.bits();
+        for i in 0..self.options.cell_bits() {
+            self.data.board.set(index + i, bits >> i & 1 != 0);
         }
 
-        Ok(())
-    }
-
-    fn get_concrete_moves(&mut self, player: player_id, moves: &mut Vec<MoveCode>) -> Result<()> {
-        let width = self.options.width;
-
-        let player = player_from_id(player);
-        if self.data.result.is_over() || player != self.data.turn {
-            return Ok(());
+        let index = self.stone_idx(pos);
+        for (player, stones) in (0..).zip(&mut self.data.stones) {
+            stones.set(index, state.player() == Some(player));
         }
 
-        for column in 0..width {
-            if self[(column, self.options.height - 1)] != State::Empty {
-                continue;
-            }
-
-            moves.push(move_code::from(column).into());
+        let (lane, depth) = self.lane_of(pos);
+        let height = self.data.heights[usize::from(lane)];
+        if state != State::Empty {
+            self.data.heights[usize::from(lane)] = height.max(depth + 1);
+        } else if depth + 1 == height {
+            self.data.heights[usize::from(lane)] = self.scan_height(lane, depth);
         }
+    }
 
-        Ok(())
+    /// Inverse of [`Self::lane_cell()`].
+    fn lane_of(&self, pos: Pos) -> (u16, u16) {
+        let (width, height) = (self.options.width, self.options.height);
+        match self.options.gravity {
+            Direction::S => (pos.0, pos.1),
+            Direction::N => (pos.0, height - 1 - pos.1),
+            Direction::W => (pos.1, pos.0),
+            Direction::E => (pos.1, width - 1 - pos.0),
+            _ => unreachable!("invalid gravity"),
+        }
     }
 
-    fn get_move_data(&mut self, _player: player_id, string: &str) -> Result<move_code> {
-        string
-            .trim()
-            .parse()
-            .map_err(|e| Error::new_dynamic(InvalidInput, format!("failed to parse move: {e}")))
+    /// Scan `lane` downwards from `depth` (exclusive) for the topmost
+    /// non-empty cell and return the [`GameData::heights`] entry.
+    fn scan_height(&self, lane: u16, depth: u16) -> u16 {
+        (0..depth)
+            .rev()
+            .find(|&d| self[self.lane_cell(lane, d)] != State::Empty)
+            .map_or(0, |d| d + 1)
     }
 
-    fn get_move_str(
-        &mut self,
-        _player: player_id,
-        mov: MoveDataSync<&move_code>,
-        str_buf: &mut ValidCString,
-    ) -> Result<()> {
-        write!(str_buf, "{}", mov.md).expect("writing move buffer failed");
-        Ok(())
+    /// Recalculate [`GameData::heights`] from the board.
+    #[cfg(test)]
+    fn reset_heights(&mut self) {
+        self.data.heights = (0..self.lanes())
+            .map(|lane| self.scan_height(lane, self.lane_depth()))
+            .collect();
     }
 
-    fn make_move(&mut self, player: player_id, mov: MoveDataSync<&move_code>) -> Result<()> {
-        let mov = (*mov.md).try_into().unwrap();
-        let pos = (mov, self.free_cell(mov));
-        self.set(pos, State::from_player_id(player));
+    /// Assert that [`GameData::heights`] matches the board.
+    fn debug_assert_heights(&self) {
+        debug_assert!(
+            (0..self.lanes()).all(|lane| {
+                self.data.heights[usize::from(lane)] == self.scan_height(lane, self.lane_depth())
+            }),
+            "height cache out of sync"
+        );
+    }
 
-        let state = State::from_player_id(player);
-        for direction in Direction::half() {
-            let mut count = 1u8;
-            count += self
-                .iter(pos, direction)
-                .enumerate()
-                .skip(1)
-                .take_while(|&(i, s)| i < self.options.length.into() && s == state)
-                .count() as u8;
-            let missing = self.options.length - count;
-            count += self
-                .iter(pos, direction.inv())
-                .skip(1)
-                .enumerate()
-                .take_while(|&(i, s)| i < missing.into() && s == state)
-                .count() as u8;
+    /// Convert [`Pos`] to the index into [`GameData::stones`].
+    fn stone_idx(&self, pos: Pos) -> usize {
+        usize::from(pos.0) * usize::from(self.options.height) + usize::from(pos.1)
+    }
 
-            if count >= self.options.length {
-                self.data.result = GameResult::Winner;
-                break;
+    /// Check whether `player` has a line which is long enough for winning.
+    ///
+    /// This shifts and ANDs the bitboard of `player` in [`GameData::stones`]
+    /// once per orientation and thus ignores wrapping lines as well as
+    /// [`GameOptions::exact`].
+    fn has_line(&self, player: u8) -> bool {
+        let stones = &self.data.stones[usize::from(player)];
+        self.masks.0.iter().any(|(mask, stride)| {
+            // Bit `i` stays set while all cells of the line from `i` so far
+            // hold stones.
+            let mut found = stones.clone();
+            for _ in 1..self.options.length {
+                if found.none() {
+                    return false;
+                }
+                found.shr_assign(*stride);
+                found.band_assign(stones);
             }
-        }
+            // Drop the lines which left the board through an edge.
+            found.band_assign(mask);
+            found.any()
+        })
+    }
 
-        if self
-            .iter((0, self.options.height - 1), Direction::E)
-            .all(|s| s != State::Empty)
-        {
-            self.data.result = GameResult::Draw;
-        }
+    /// Number of columns into which stones can be dropped.
+    ///
+    /// With sideways gravity, these are the rows of the board.
+    fn lanes(&self) -> u16 {
+        self.options.lanes()
+    }
 
-        if !self.data.result.is_over() {
-            self.data.turn = !self.data.turn;
+    /// Number of cells in each of the [`Self::lanes()`].
+    fn lane_depth(&self) -> u16 {
+        match self.options.gravity {
+            Direction::N | Direction::S => self.options.height,
+            _ => self.options.width,
         }
-
-        Ok(())
     }
 
-    fn get_results(&mut self, players: &mut Vec<player_id>) -> Result<()> {
-        if let GameResult::Winner = self.data.result {
-            players.push(player_to_id(self.data.turn));
+    /// Return the position of the `depth`th cell in `lane`.
+    ///
+    /// Depth zero is the cell in which a dropped stone comes to rest first.
+    fn lane_cell(&self, lane: u16, depth: u16) -> Pos {
+        let (width, height) = (self.options.width, self.options.height);
+        match self.options.gravity {
+            Direction::S => (lane, depth),
+            Direction::N => (lane, height - 1 - depth),
+            Direction::W => (depth, lane),
+            Direction::E => (width - 1 - depth, lane),
+            _ => unreachable!("invalid gravity"),
         }
-
-        Ok(())
     }
 
-    fn is_legal_move(&mut self, player: player_id, mov: MoveDataSync<&move_code>) -> Result<()> {
-        // Assert unsigned type
-        assert_eq!(0, move_code::MIN);
-
-        if *mov.md >= self.options.width.into() {
-            return Err(Error::new_static(InvalidInput, "column does not exist\0"));
-        }
-        if self.data.result.is_over() {
-            return Err(Error::new_static(InvalidInput, "game is already over\0"));
-        }
-        if self.data.turn != player_from_id(player) {
-            return Err(Error::new_static(InvalidInput, "not this player's turn\0"));
+    /// Move all stones starting from `pos` and going in `direction` one field
+    /// towards `pos` and put `fill` into the last field.
+    ///
+    /// Stops in front of the first blocked cell.
+    fn shift(&mut self, pos: Pos, direction: Direction, fill: State) {
+        let count = self
+            .positions(pos, direction)
+            .take_while(|&p| self[p] != State::Blocked)
+            .count();
+        let mut cells = self.positions(pos, direction).take(count);
+        let mut previous = cells.next().unwrap();
+        for current in cells {
+            let state = self[current];
+            self.set(previous, state);
+            previous = current;
         }
+        self.set(previous, fill);
+    }
 
-        if let State::Empty = self[(*mov.md as u8, self.options.height - 1)] {
-            Ok(())
-        } else {
-            Err(Error::new_static(InvalidInput, "column full\0"))
-        }
+    /// Iterate through the positions of the game board starting at `pos` and
+    /// going in `direction`.
+    fn positions(&self, pos: Pos, direction: Direction) -> impl Iterator<Item = Pos> {
+        let (width, height) = (self.options.width, self.options.height);
+        iter::successors(Some(pos), move |&p| direction.walk(p, width, height))
     }
 
-    fn print(&mut self, _player: player_id, str_buf: &mut ValidCString) -> Result<()> {
-        const ERROR: &str = "writing print buffer failed";
-        let col_chars = self.options.col_chars();
-
-        for y in (0..self.options.height).rev() {
-            for state in self.iter((0, y), Direction::E) {
-                write!(str_buf, "|{state:col_chars$}").expect(ERROR);
+    /// Iterate through the positions of a potential line starting at `pos`
+    /// and going in `direction`.
+    ///
+    /// Unlike [`Self::positions()`], this wraps around the edges of the board
+    /// if enabled by the options.
+    /// Each position is visited at most once.
+    fn line_positions(&self, pos: Pos, direction: Direction) -> impl Iterator<Item = Pos> {
+        let (width, height) = (self.options.width, self.options.height);
+        let wrap = self.options.wrap;
+        let cells = if wrap {
+            self.line_cells(direction)
+        } else {
+            usize::MAX
+        };
+        iter::successors(Some(pos), move |&p| {
+            if wrap {
+                Some(direction.walk_wrapping(p, width, height))
+            } else {
+                direction.walk(p, width, height)
             }
-            writeln!(str_buf, "|").expect(ERROR);
-        }
-        for x in 0..self.options.width {
-            write!(str_buf, " {x:>col_chars$}").expect(ERROR);
-        }
-        writeln!(str_buf, " ").expect(ERROR);
-
-        Ok(())
+        })
+        .take(cells)
     }
-}
 
-impl Index<Pos> for ConnectFour {
-    type Output = State;
+    /// Number of distinct cells on a wrapping line going in `direction`.
+    fn line_cells(&self, direction: Direction) -> usize {
+        let (width, height) = (self.options.width.into(), self.options.height.into());
+        match direction {
+            Direction::N | Direction::S => height,
+            Direction::E | Direction::W => width,
+            _ => width / gcd(width, height) * height,
+        }
+    }
 
-    /// Return board state at `pos`.
-    fn index(&self, pos: Pos) -> &Self::Output {
-        if !self.data.board[self.idx(pos)] {
-            &State::Empty
-        } else if !self.data.board[self.idx(pos) + 1] {
-            &State::X
-        } else {
-            &State::O
+    /// Iterate through the fields of the game board starting at `pos` and
+    /// going in `direction`.
+    fn iter(&self, pos: Pos, direction: Direction) -> DirectionIter {
+        DirectionIter {
+            game: self,
+            current: Some(pos),
+            direction,
         }
     }
-}
 
-/// Column × Row
-pub(crate) type Pos = (u8, u8);
+    /// Turn `target` into a copy of `self` even if its options differ.
+    ///
+    /// Unlike [`GameMethods::copy_from()`], this also copies the options.
+    /// The allocations of `target` are reused where their capacities suffice.
+    #[cfg(test)]
+    pub(crate) fn clone_config_into(&self, target: &mut Self) {
+        target.options = self.options;
+        target.zobrist.clone_from(&self.zobrist);
+        target.masks.clone_from(&self.masks);
+
+        target.data.copy_resized_from(&self.data);
+    }
 
-/// The state of a single field of the game board.
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
-pub(crate) enum State {
-    /// No piece at this position
-    Empty,
-    /// Player 1
-    X,
-    /// Player 2
-    O,
-}
+    /// Return the Zobrist hash of the current position.
+    ///
+    /// Equal positions always have the same id, regardless of the move order
+    /// which led to them.
+    /// This is for internal use only since the _surena_ bindings offer no hook
+    /// for position ids.
+    #[allow(dead_code)]
+    pub(crate) fn id(&self) -> u64 {
+        self.fold_state(self.data.hash)
+    }
 
-impl State {
-    /// Convert a [`player_id`] (1 or 2) to a state ([`State::X`] or
-    /// [`State::O`]).
+    /// Return a stable hash of the move `mov`.
     ///
-    /// # Panics
-    /// Panics when given numbers other than 1 or 2.
-    fn from_player_id(player: player_id) -> Self {
-        match player {
-            1 => Self::X,
-            2 => Self::O,
-            _ => unreachable!("invalid player id"),
-        }
+    /// The hash only depends on the move code, not on the position or the
+    /// options, and differs for every move code.
+    #[cfg(test)]
+    pub(crate) fn get_move_hash(&self, mov: move_code) -> u64 {
+        // SplitMix64 is a bijection, so different codes never collide.
+        SplitMix64(MOVE_HASH_SEED ^ mov).next()
     }
-}
 
-impl Display for State {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let c = match self {
-            Self::Empty => ' ',
-            Self::X => 'X',
-            Self::O => 'O',
-        };
-        for _ in 0..f.width().unwrap_or(1) {
-            write!(f, "{c}")?;
+    /// Same as [`Self::id()`] but positions which are horizontal mirrors of
+    /// each other (see `Self::mirror_horizontal()`) share the same id.
+    ///
+    /// This is the smaller of the ids of the position and its mirror.
+    #[allow(dead_code)]
+    pub(crate) fn canonical_id(&self) -> u64 {
+        let width = self.options.width;
+        let mut mirrored = 0;
+        for y in 0..self.options.height {
+            for x in 0..width {
+                let index = self.stone_idx((width - 1 - x, y));
+                mirrored ^= self.zobrist.cell(index, self[(x, y)]);
+            }
         }
-        Ok(())
+        self.id().min(self.fold_state(mirrored))
     }
-}
 
-/// Direction on the game board.
-///
-/// North is up and east is right.
-#[derive(Clone, Copy)]
-enum Direction {
-    N,
-    NE,
-    E,
-    SE,
-    S,
-    SW,
-    W,
-    NW,
-}
+    /// Fold [`GameData::turn`] and [`GameData::result`] into the board
+    /// `hash`.
+    fn fold_state(&self, mut hash: u64) -> u64 {
+        hash ^= self.zobrist.turns[usize::from(self.data.turn)];
+        match self.data.result {
+            GameResult::Ongoing => (),
+            GameResult::Winner => hash ^= self.zobrist.winner,
+            GameResult::Draw => hash ^= self.zobrist.draw,
+        }
+        hash
+    }
 
-impl Direction {
-    /// Returns the next position from `pos` in direction `self`.
+    /// Take back the last move played via [`GameMethods::make_move()`].
     ///
-    /// `width` and `height` are used to perform bounds checking.
-    fn walk(&self, pos: Pos, width: u8, height: u8) -> Option<Pos> {
-        let mut next = pos;
-        match self {
-            Self::N => {
-                next.1 += 1;
-                if next.1 >= height {
-                    return None;
-                }
-            }
-            Self::NE => {
-                next.0 += 1;
-                next.1 += 1;
-
-                if next.0 >= width || next.1 >= height {
-                    return None;
-                }
+    /// Returns the code of the undone move or [`None`] if there is no move
+    /// to undo.
+    /// Stones placed by [`GameMethods::import_state()`] cannot be undone.
+    /// Afterwards, `Self::id()` is the same as before the move.
+    #[cfg(any(feature = "mirabel", test))]
+    pub(crate) fn undo_move(&mut self) -> Option<move_code> {
+        let played = self.data.history.pop()?;
+        match played.action {
+            Action::Drop(column) => {
+                let height = self.data.heights[usize::from(column)];
+                self.set(self.lane_cell(column, height - 1), State::Empty);
             }
-            Self::E => {
-                next.0 += 1;
-
-                if next.0 >= width {
-                    return None;
-                }
+            Action::Pop(column) => {
+                let end = self
+                    .positions(self.lane_cell(column, 0), self.options.gravity.inv())
+                    .take_while(|&p| self[p] != State::Blocked)
+                    .last()
+                    .unwrap();
+                self.shift(end, self.options.gravity, State::from_turn(played.player));
             }
-            Self::SE => {
-                next.0 += 1;
-                next.1 = next.1.checked_sub(1)?;
+        }
 
-                if next.0 >= width {
-                    return None;
-                }
-            }
-            Self::S => {
-                next.1 = next.1.checked_sub(1)?;
-            }
-            Self::SW => {
-                next.0 = next.0.checked_sub(1)?;
-                next.1 = next.1.checked_sub(1)?;
-            }
-            Self::W => {
-                next.0 = next.0.checked_sub(1)?;
-            }
-            Self::NW => {
-                next.0 = next.0.checked_sub(1)?;
-                next.1 += 1;
+        self.data.turn = played.player;
+        self.data.moves = self.data.moves.wrapping_sub(1);
+        self.data.result = GameResult::Ongoing;
+        self.data.winning_line.clear();
+        self.debug_assert_heights();
 
-                if next.1 >= height {
-                    return None;
-                }
-            }
-        }
-        Some(next)
+        Some(played.action.code())
     }
 
-    /// Returns the opposite direction.
-    const fn inv(&self) -> Self {
-        match self {
-            Self::N => Self::S,
-            Self::NE => Self::SW,
-            Self::E => Self::W,
-            Self::SE => Self::NW,
-            Self::S => Self::N,
-            Self::SW => Self::NE,
-            Self::W => Self::E,
-            Self::NW => Self::SE,
+    /// Return the cells forming the winning line if the last move won the
+    /// game.
+    ///
+    /// Returns [`None`] for ongoing games, draws, and wins which were
+    /// imported.
+    #[cfg(test)]
+    pub(crate) fn winning_line(&self) -> Option<&[Pos]> {
+        if self.data.winning_line.is_empty() {
+            None
+        } else {
+            Some(&self.data.winning_line)
         }
     }
 
-    /// Returns one half of the available directions.
-    const fn half() -> [Self; 4] {
-        [Self::N, Self::NE, Self::E, Self::SE]
+    /// Return the column (or row with sideways gravity) of the last move.
+    ///
+    /// Falls back to the column given by [`GameMethods::import_state()`]
+    /// before the first move after the import.
+    #[cfg(any(feature = "mirabel", test))]
+    pub(crate) fn last_column(&self) -> Option<u16> {
+        self.data
+            .history
+            .last()
+            .map(|played| played.action.column())
+            .or(self.data.imported_last)
     }
-}
-
-struct DirectionIter<'g> {
-    game: &'g ConnectFour,
-    direction: Direction,
-    current: Option<Pos>,
-}
-
-impl<'g> Iterator for DirectionIter<'g> {
-    type Item = State;
-
-    /// Returns the state of the next field in the given direction.
-    fn next(&mut self) -> Option<Self::Item> {
-        let current = self.current;
-        self.current =
-            self.direction
-                .walk(current?, self.game.options.width, self.game.options.height);
 
-        current.map(|c| self.game[c])
+    /// Return the position of the topmost stone in [`Self::last_column()`].
+    ///
+    /// Returns [`None`] if the last move is unknown or emptied its column.
+    #[cfg(feature = "mirabel")]
+    pub(crate) fn last_stone(&self) -> Option<Pos> {
+        let column = self.last_column()?;
+        let height = self.data.heights[usize::from(column)];
+        (height != 0).then(|| self.lane_cell(column, height - 1))
     }
-}
-
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
-pub(crate) struct GameOptions {
-    width: u8,
-    height: u8,
-    /// The number of successive stones needed for victory.
-    length: u8,
-}
 
-impl GameOptions {
-    /// Create a new instance of game options from an option string.
+    /// Return the position of every stone dropped since the last state
+    /// import together with the number of the move which dropped it.
     ///
-    /// Accepts options in the following format: `7x6@4`.
-    /// The option string consists of three separate numbers: the column count,
-    /// the row count, and the minimum number of connected pieces for winning.
-    fn new(options: &str) -> Result<Self> {
-        let mut numbers = options.trim().split(|c: char| !c.is_ascii_digit());
-        let width = parse("width", numbers.next())?;
-        let height = parse("height", numbers.next())?;
-        let length = parse("length", numbers.next())?;
-        if let Some(excess) = numbers.next() {
-            return Err(Error::new_dynamic(
-                InvalidInput,
-                format!(r#"unexpected "{excess}" after options"#),
-            ));
+    /// Move numbers continue `Self::ply_count()` of the imported state.
+    /// Stones which were imported or popped out are left out.
+    #[cfg(feature = "mirabel")]
+    pub(crate) fn move_numbers(&self) -> Vec<(Pos, u16)> {
+        let mut game = self.clone();
+        while game.undo_move().is_some() {}
+
+        let mut numbers =
+            vec![None; usize::from(self.options.width) * usize::from(self.options.height)];
+        let mut ply = game.data.moves;
+        for played in &self.data.history {
+            ply = ply.wrapping_add(1);
+            match played.action {
+                Action::Drop(column) => numbers[game.stone_idx(game.free_cell(column))] = Some(ply),
+                Action::Pop(column) => {
+                    // Shift the numbers like the stones in [`Self::pop_stone()`].
+                    let cells: Vec<_> = game
+                        .positions(game.lane_cell(column, 0), game.options.gravity.inv())
+                        .take_while(|&p| game[p] != State::Blocked)
+                        .map(|p| game.stone_idx(p))
+                        .collect();
+                    for pair in cells.windows(2) {
+                        numbers[pair[0]] = numbers[pair[1]];
+                    }
+                    numbers[*cells.last().unwrap()] = None;
+                }
+            }
+            game.play(player_to_id(played.player), played.action);
         }
 
-        if width < 1 || height < 1 || length < 1 {
-            return Err(Error::new_static(
-                InvalidOptions,
-                "width, height, and length need to be at least 1\0",
-            ));
-        };
-        if length > width && length > height {
-            return Err(Error::new_static(
-                InvalidOptions,
-                "length must not exceed both width and height\0",
-            ));
-        }
+        (0..self.options.width)
+            .flat_map(|x| (0..self.options.height).map(move |y| (x, y)))
+            .filter_map(|pos| Some((pos, numbers[self.stone_idx(pos)]?)))
+            .collect()
+    }
 
-        Ok(Self {
-            width,
-            height,
-            length,
-        })
+    /// Check whether the game has ended, either with a winner or in a draw.
+    ///
+    /// This tells draws apart from ongoing games, for which
+    /// [`GameMethods::get_results()`] is empty too.
+    #[cfg(any(feature = "mirabel", test))]
+    pub(crate) fn is_terminal(&self) -> bool {
+        self.data.result.is_over()
     }
 
-    /// Number of character required to print the largest column index.
+    /// Return the score of every player in turn order or [`None`] while the
+    /// game is ongoing.
     ///
-    /// Column indices start from zero.
-    fn col_chars(&self) -> usize {
-        match self.width {
-            0..=10 => 1,
-            11..=100 => 2,
-            101..=u8::MAX => 3,
+    /// The winner scores 1 and everybody else -1 (e.g., `[1, -1]` for a win
+    /// of `X`).
+    /// All players score 0 in a draw.
+    /// Complements [`GameMethods::get_results()`].
+    #[cfg(test)]
+    pub(crate) fn scores(&self) -> Option<Vec<i8>> {
+        let players = 0..self.options.players;
+        match self.data.result {
+            GameResult::Ongoing => None,
+            GameResult::Winner => Some(
+                players
+                    .map(|p| if p == self.data.turn { 1 } else { -1 })
+                    .collect(),
+            ),
+            GameResult::Draw => Some(players.map(|_| 0).collect()),
         }
     }
 
-    /// Width of the board.
-    #[cfg(feature = "mirabel")]
-    pub(crate) fn width(&self) -> u8 {
-        self.width
+    /// Return the number of moves played.
+    ///
+    /// Stones placed by [`GameMethods::import_state()`] count as one move
+    /// each unless the state starts with a move number.
+    /// Wraps around for extremely long _Pop Out_ games.
+    #[cfg(test)]
+    pub(crate) fn ply_count(&self) -> u16 {
+        self.data.moves
     }
 
-    /// Height of the board.
-    #[cfg(feature = "mirabel")]
-    pub(crate) fn height(&self) -> u8 {
-        self.height
+    /// Write all moves played since the last state import separated by
+    /// spaces (e.g., `4 3 p4 5`).
+    ///
+    /// Uses the same format as [`GameMethods::get_move_str()`].
+    #[cfg(test)]
+    pub(crate) fn export_move_list(&self, str_buf: &mut ValidCString) {
+        const ERROR: &str = "writing move list failed";
+
+        for (i, played) in self.data.history.iter().enumerate() {
+            if i != 0 {
+                write!(str_buf, " ").expect(ERROR);
+            }
+            match played.action {
+                Action::Drop(column) => write!(str_buf, "{}", self.options.column_label(column)),
+                Action::Pop(column) => write!(str_buf, "p{}", self.options.column_label(column)),
+            }
+            .expect(ERROR);
+        }
     }
-}
 
-impl Default for GameOptions {
-    fn default() -> Self {
-        Self {
-            width: DEFAULT_WIDTH,
-            height: DEFAULT_HEIGHT,
-            length: DEFAULT_LENGTH,
+    /// Play the whitespace-separated moves in `string` from the current
+    /// position.
+    ///
+    /// Moves are parsed with [`GameMethods::get_move_data()`].
+    ///
+    /// # Errors
+    /// Fails with [`InvalidInput`] and leaves the game unchanged if any move
+    /// cannot be parsed or is illegal.
+    #[cfg(test)]
+    pub(crate) fn import_move_list(&mut self, string: &str) -> Result<()> {
+        let mut game = self.clone();
+        for token in string.split_whitespace() {
+            let player = player_to_id(game.data.turn);
+            let code = game.get_move_data(player, token)?;
+            game.is_legal_move(player, MoveDataSync::with_default(&code))?;
+            game.make_move(player, MoveDataSync::with_default(&code))?;
         }
+
+        *self = game;
+        Ok(())
     }
-}
 
-#[derive(Clone, PartialEq, Eq, Debug)]
-struct GameData {
-    /// Every two bits describe a single field.
+    /// Same as [`GameMethods::import_state()`] but also checks that only the
+    /// winner has a line (see [`Self::scan_for_win()`]) and that the numbers
+    /// of stones of the players fit the player to move.
     ///
-    /// The first of these bits signals if the field is even occupied.
-    /// The second one signals the piece color if occupied.
-    board: BitVec,
-    /// `false` → `X` and `true` → `O`
-    turn: bool,
-    result: GameResult,
-}
+    /// In misère mode, the line belongs to the loser instead.
+    /// Players take turns starting with [`GameOptions::first_player`], so the
+    /// players who already moved in the current round have one stone more
+    /// than the others.
+    /// The stone counts are not checked in the _Pop Out_ variant because
+    /// popping breaks them and so do handicap stones.
+    ///
+    /// # Errors
+    /// Fails like [`GameMethods::import_state()`] and with [`InvalidInput`] if
+    /// the winner has no line, if a game without winner has a line, or if the
+    /// stone counts do not fit.
+    #[cfg(test)]
+    pub(crate) fn import_state_strict(&mut self, string: Option<&str>) -> Result<()> {
+        self.import_state(string)?;
+
+        // In misère mode, the loser owns the line.
+        let owner = match self.data.result {
+            GameResult::Winner if self.options.misere => Some(self.next_player(self.data.turn)),
+            GameResult::Winner => Some(self.data.turn),
+            _ => None,
+        };
+        match owner {
+            Some(owner) if self.find_line(State::from_turn(owner)).is_none() => {
+                return Err(ErrorKind::MissingLine.error("game won without a line"));
+            }
+            None if self.scan_for_win().is_some() => {
+                return Err(ErrorKind::UnexpectedLine.error("line in a game without winner"));
+            }
+            _ => (),
+        }
 
-impl GameData {
-    fn new(options: &GameOptions) -> Self {
-        let board = BitVec::new(2 * usize::from(options.width) * usize::from(options.height));
-        Self {
-            board,
-            turn: false,
-            result: GameResult::Ongoing,
+        if self.options.pop_out || self.options.handicap != 0 {
+            return Ok(());
         }
-    }
 
-    fn copy_from(&mut self, other: &Self) {
-        self.board.copy_from_bitvec(&other.board);
-        self.turn = other.turn;
-        self.result = other.result;
-    }
+        let mut counts = vec![0_usize; usize::from(self.options.players)];
+        for pos in
+            (0..self.options.height).flat_map(|y| (0..self.options.width).map(move |x| (x, y)))
+        {
+            if let Some(player) = self[pos].player() {
+                counts[usize::from(player)] += 1;
+            }
+        }
 
-    fn reset(&mut self) {
-        self.board.reset();
-        self.turn = false;
-        self.result = GameResult::Ongoing;
+        // Who would play next?
+        let next = match self.data.result {
+            GameResult::Ongoing => Some(self.data.turn),
+            // In misère mode, the loser made the last move.
+            GameResult::Winner if self.options.misere => Some(self.data.turn),
+            GameResult::Winner => Some(self.next_player(self.data.turn)),
+            GameResult::Draw => None,
+        };
+        // The players before `next` in turn order starting with the first
+        // player have one stone more than the others.
+        let (first, players) = (self.options.first_player, self.options.players);
+        let rank = |player: u8| (player + players - first) % players;
+        let base = counts[usize::from((first + players - 1) % players)];
+        let fits = |next: u8| {
+            (0..)
+                .zip(&counts)
+                .all(|(player, &count)| count == base + usize::from(rank(player) < rank(next)))
+        };
+        let valid = match next {
+            Some(next) => fits(next),
+            None => (0..self.options.players).any(fits),
+        };
+        if !valid {
+            let counts: Vec<_> = counts
+                .iter()
+                .zip(PLAYER_CHARS)
+                .map(|(count, c)| format!("{count} {c}"))
+                .collect();
+            return Err(Error::new_dynamic(
+                InvalidInput,
+                format!(
+                    "{} stones do not fit the player to move",
+                    counts.join(" and ")
+                ),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Same as [`GameMethods::export_state()`] but in the given `format`.
+    #[cfg(test)]
+    pub(crate) fn export_state_as(
+        &mut self,
+        player: player_id,
+        format: StateFormat,
+        str_buf: &mut ValidCString,
+    ) -> Result<()> {
+        if format == StateFormat::Plain {
+            return self.export_state(player, str_buf);
+        }
+
+        let mut plain = ValidCString::default();
+        self.export_state(player, &mut plain)?;
+        let (board, rest) = plain
+            .as_ref()
+            .split_once('#')
+            .expect("state without hashtag");
+        if format == StateFormat::Compact {
+            write!(str_buf, "{}#{rest}", board.trim_end_matches('/'))
+                .expect("writing state buffer failed");
+            return Ok(());
+        }
+
+        let mut chars = board.chars().peekable();
+        while let Some(c) = chars.next() {
+            let mut count = 1;
+            while chars.next_if_eq(&c).is_some() {
+                count += 1;
+            }
+            if count == 1 {
+                write!(str_buf, "{c}")
+            } else {
+                write!(str_buf, "{c}{count}")
+            }
+            .expect("writing state buffer failed");
+        }
+        write!(str_buf, "#{rest}").expect("writing state buffer failed");
+
+        Ok(())
+    }
+
+    /// Same as [`GameMethods::import_state()`] but in the given `format`.
+    ///
+    /// # Errors
+    /// Besides the errors of [`GameMethods::import_state()`], fails if a run
+    /// of [`StateFormat::RunLength`] does not fit into its column or onto
+    /// the board.
+    #[cfg(test)]
+    pub(crate) fn import_state_as(
+        &mut self,
+        format: StateFormat,
+        string: Option<&str>,
+    ) -> Result<()> {
+        let Some(string) = string.filter(|_| format == StateFormat::RunLength) else {
+            return self.import_state(string);
+        };
+
+        let (prefix, string) = match string.split_once(':') {
+            Some((prefix, board)) => (Some(prefix), board),
+            None => (None, string),
+        };
+        let (board, rest) = match string.split_once('#') {
+            Some((board, rest)) => (board, Some(rest)),
+            None => (string, None),
+        };
+
+        let mut expanded = String::new();
+        if let Some(prefix) = prefix {
+            expanded.push_str(prefix);
+            expanded.push(':');
+        }
+        let mut chars = board.chars().peekable();
+        let mut depth = 0;
+        let mut lane = 0;
+        while let Some(c) = chars.next() {
+            if c.is_ascii_whitespace() {
+                expanded.push(c);
+                continue;
+            }
+            if c.is_ascii_digit() {
+                return Err(ErrorKind::Number.error("run length without cell"));
+            }
+
+            let mut digits = String::new();
+            while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+                digits.push(digit);
+            }
+            let count: u16 = if digits.is_empty() {
+                1
+            } else {
+                parse("run length", Some(&digits))?
+            };
+            if count == 0 {
+                return Err(ErrorKind::Number.error("run length must be at least 1"));
+            }
+
+            if c == '/' {
+                lane += u32::from(count);
+                depth = 0;
+                if lane >= u32::from(self.lanes()) {
+                    return Err(ErrorKind::TooManyColumns.error("state has too many columns"));
+                }
+            } else {
+                depth += u32::from(count);
+                if depth > u32::from(self.lane_depth()) {
+                    return Err(ErrorKind::TooManyRows.error("run exceeds the column height"));
+                }
+            }
+            expanded.extend(iter::repeat_n(c, count.into()));
+        }
+        if let Some(rest) = rest {
+            expanded.push('#');
+            expanded.push_str(rest);
+        }
+
+        self.import_state(Some(&expanded))
+    }
+
+    /// Reflect the board horizontally, swapping column `x` with column
+    /// `width - 1 - x`.
+    ///
+    /// Sideways gravity is reflected too.
+    #[cfg(test)]
+    pub(crate) fn mirror_horizontal(&mut self) {
+        let width = self.options.width;
+        let gravity = match self.options.gravity {
+            Direction::W => Direction::E,
+            Direction::E => Direction::W,
+            gravity => gravity,
+        };
+        let lanes = self.lanes();
+        let vertical = matches!(gravity, Direction::N | Direction::S);
+        self.transform(
+            |(x, y)| (width - 1 - x, y),
+            gravity,
+            |lane| if vertical { lanes - 1 - lane } else { lane },
+        );
+    }
+
+    /// Rotate the board by 180 degrees, reflecting both axes.
+    ///
+    /// Gravity is rotated too, so _Connect Four_ is played upside down
+    /// afterwards.
+    #[cfg(test)]
+    pub(crate) fn rotate_180(&mut self) {
+        let (width, height) = (self.options.width, self.options.height);
+        let gravity = match self.options.gravity {
+            Direction::S => Direction::N,
+            Direction::N => Direction::S,
+            Direction::W => Direction::E,
+            Direction::E => Direction::W,
+            _ => unreachable!("invalid gravity"),
+        };
+        let lanes = self.lanes();
+        self.transform(
+            |(x, y)| (width - 1 - x, height - 1 - y),
+            gravity,
+            |lane| lanes - 1 - lane,
+        );
+    }
+
+    /// Move every cell from `pos` to `map_pos(pos)` and set the new
+    /// `gravity`.
+    ///
+    /// `map_lane` maps the lanes of the recorded moves accordingly.
+    #[cfg(test)]
+    fn transform(
+        &mut self,
+        map_pos: impl Fn(Pos) -> Pos,
+        gravity: Direction,
+        map_lane: impl Fn(u16) -> u16,
+    ) {
+        let cells: Vec<_> = (0..self.options.height)
+            .flat_map(|y| (0..self.options.width).map(move |x| (x, y)))
+            .map(|pos| (pos, self[pos]))
+            .collect();
+        for (pos, state) in cells {
+            self.set(map_pos(pos), state);
+        }
+
+        self.options.gravity = gravity;
+        self.reset_heights();
+        for pos in &mut self.data.winning_line {
+            *pos = map_pos(*pos);
+        }
+        for played in &mut self.data.history {
+            played.action = match played.action {
+                Action::Drop(lane) => Action::Drop(map_lane(lane)),
+                Action::Pop(lane) => Action::Pop(map_lane(lane)),
+            };
+        }
+    }
+
+    /// Provide read-only access to the internal options.
+    #[cfg(any(feature = "mirabel", feature = "export"))]
+    pub(crate) fn options(&self) -> &GameOptions {
+        &self.options
+    }
+
+    /// Return the current state of the game.
+    #[cfg(feature = "mirabel")]
+    pub(crate) fn result(&self) -> GameResult {
+        self.data.result
+    }
+
+    /// Return the winner if any.
+    #[cfg(feature = "mirabel")]
+    pub(crate) fn winner(&self) -> Option<u8> {
+        (self.data.result == GameResult::Winner).then_some(self.data.turn)
+    }
+
+    /// Return who is currently to move.
+    ///
+    /// # Panics
+    /// Panics if the game is over.
+    #[cfg(feature = "mirabel")]
+    pub(crate) fn turn(&self) -> u8 {
+        assert!(
+            matches!(self.data.result, GameResult::Ongoing),
+            "game is already over"
+        );
+        self.data.turn
+    }
+
+    /// Check if a move can be performed in this column.
+    ///
+    /// # Panics
+    /// Panics if the column id is invalid.
+    pub(crate) fn possible_move(&self, column: u16) -> bool {
+        !self.data.result.is_over() && self.data.heights[usize::from(column)] < self.lane_depth()
+    }
+
+    /// Same as [`GameMethods::is_legal_move()`] but without describing why a
+    /// move is illegal.
+    ///
+    /// This never allocates, so searches can check many moves cheaply.
+    ///
+    /// # Panics
+    /// Panics if the game is ongoing, `mov` exists, and `player` is invalid.
+    pub(crate) fn is_legal(&self, player: player_id, mov: move_code) -> bool {
+        let action = Action::from_code(mov).filter(|a| a.column() < self.lanes());
+        let Some(action) = action else {
+            return false;
+        };
+        if self.data.result.is_over() || self.data.turn != player_from_id(player) {
+            return false;
+        }
+
+        match action {
+            Action::Drop(column) => {
+                self[self.lane_cell(column, self.lane_depth() - 1)] == State::Empty
+            }
+            Action::Pop(column) => {
+                self.options.pop_out
+                    && self[self.lane_cell(column, 0)] == State::from_turn(self.data.turn)
+            }
+        }
+    }
+
+    /// Check if no more stones can be dropped into any column.
+    ///
+    /// This is independent of the game result.
+    pub(crate) fn board_is_full(&self) -> bool {
+        let top = self.lane_depth() - 1;
+        (0..self.lanes()).all(|l| self[self.lane_cell(l, top)] != State::Empty)
+    }
+
+    /// Count the stones on the board.
+    ///
+    /// Blocked cells are not counted.
+    #[cfg(test)]
+    pub(crate) fn occupied(&self) -> usize {
+        self.data.stones.iter().map(BitVec::count_ones).sum()
+    }
+
+    /// Count the stones which can still be dropped before the board is full
+    /// (see [`Self::board_is_full()`]).
+    ///
+    /// Empty cells below blocked cells can never be filled and are therefore
+    /// not counted.
+    #[cfg(test)]
+    pub(crate) fn remaining(&self) -> usize {
+        let depth = self.lane_depth();
+        self.data
+            .heights
+            .iter()
+            .map(|&height| usize::from(depth - height))
+            .sum()
+    }
+
+    /// Return a bit mask of the columns into which a stone can be dropped.
+    ///
+    /// Bit `i` is set iff column `i` is not full and the game is ongoing.
+    /// Only supports up to 64 columns.
+    #[cfg(any(feature = "rand", test))]
+    pub(crate) fn playable_columns_mask(&self) -> u64 {
+        debug_assert!(self.lanes() <= 64, "too many columns for mask");
+        (0..self.lanes())
+            .filter(|&column| self.possible_move(column))
+            .fold(0, |mask, column| mask | 1 << column)
+    }
+
+    /// Pick a stone drop uniformly at random among all possible ones.
+    ///
+    /// Returns [`None`] if the game is over or the board is full.
+    #[cfg(feature = "rand")]
+    #[allow(dead_code)]
+    pub(crate) fn random_move<R: rand::Rng>(&self, rng: &mut R) -> Option<move_code> {
+        if self.lanes() > 64 {
+            let columns: Vec<_> = (0..self.lanes())
+                .filter(|&column| self.possible_move(column))
+                .collect();
+            return columns
+                .get(rng.gen_range(0..columns.len().max(1)))
+                .map(|&column| column.into());
+        }
+
+        let mut mask = self.playable_columns_mask();
+        if mask == 0 {
+            return None;
+        }
+        for _ in 0..rng.gen_range(0..mask.count_ones()) {
+            // Clear the lowest set bit.
+            mask &= mask - 1;
+        }
+        Some(mask.trailing_zeros().into())
+    }
+
+    /// Like [`GameMethods::get_concrete_moves()`] but orders the drops from
+    /// the center column outwards.
+    ///
+    /// Ties are broken towards the left.
+    /// This ordering improves pruning in alpha-beta search.
+    #[cfg(test)]
+    pub(crate) fn get_concrete_moves_ordered(
+        &mut self,
+        player: player_id,
+        moves: &mut Vec<MoveCode>,
+    ) -> Result<()> {
+        let player = player_from_id(player);
+        if self.data.result.is_over() || player != self.data.turn {
+            return Ok(());
+        }
+
+        for column in center_out(self.lanes()) {
+            if self.possible_move(column) {
+                moves.push(move_code::from(column).into());
+            }
+        }
+
+        self.for_each_pop(player, |code| moves.push(code.into()));
+
+        Ok(())
+    }
+
+    /// Call `f` with all possible pops of `player` if this is the _Pop Out_
+    /// variant.
+    fn for_each_pop(&self, player: u8, mut f: impl FnMut(move_code)) {
+        if !self.options.pop_out {
+            return;
+        }
+
+        let state = State::from_turn(player);
+        for column in 0..self.lanes() {
+            if self[self.lane_cell(column, 0)] == state {
+                f(Action::Pop(column).code());
+            }
+        }
+    }
+
+    /// Call `f` with every legal move of the player to move without
+    /// allocating.
+    ///
+    /// The moves are the same and in the same order as those of
+    /// [`GameMethods::get_concrete_moves()`].
+    pub(crate) fn for_each_legal_move(&self, mut f: impl FnMut(move_code)) {
+        if self.data.result.is_over() {
+            return;
+        }
+
+        for column in 0..self.lanes() {
+            if self.possible_move(column) {
+                f(column.into());
+            }
+        }
+        self.for_each_pop(self.data.turn, f);
+    }
+
+    /// Count the legal moves of the player to move without allocating.
+    #[cfg(test)]
+    pub(crate) fn legal_move_count(&self) -> usize {
+        let mut count = 0;
+        self.for_each_legal_move(|_| count += 1);
+        count
+    }
+
+    /// Drop a stone of `player` into `column` and return the resulting state
+    /// of the game.
+    ///
+    /// Like [`GameMethods::make_move()`], this does not check whether the move
+    /// is legal.
+    #[cfg(test)]
+    pub(crate) fn make_move_checked(&mut self, player: player_id, column: u16) -> GameResult {
+        self.play(player, Action::Drop(column));
+        self.data.result
+    }
+
+    /// Count the leaf positions of the game tree `depth` moves deep.
+    ///
+    /// Positions where the game ends early count as a single leaf.
+    #[cfg(test)]
+    pub(crate) fn perft(&mut self, depth: u32) -> u64 {
+        if depth == 0 || self.data.result.is_over() {
+            return 1;
+        }
+
+        let player = player_to_id(self.data.turn);
+        let mut moves = vec![];
+        self.get_concrete_moves(player, &mut moves)
+            .expect("generating moves failed");
+
+        let mut leaves = 0;
+        for code in MoveCode::slice_to_rust(&moves) {
+            let action = Action::from_code(code).expect("column does not exist");
+            self.play(player, action);
+            leaves += self.perft(depth - 1);
+            self.undo_move();
+        }
+        leaves
+    }
+
+    /// Perform `action` for `player` without checking its legality.
+    fn play(&mut self, player: player_id, action: Action) {
+        self.data.moves = self.data.moves.wrapping_add(1);
+        self.data.history.push(Played {
+            action,
+            player: player_from_id(player),
+        });
+
+        match action {
+            Action::Drop(column) => self.drop_stone(column, State::from_player_id(player)),
+            Action::Pop(column) => self.pop_stone(column, player_from_id(player)),
+        }
+
+        match self.data.result {
+            GameResult::Ongoing => self.data.turn = self.next_player(self.data.turn),
+            // The player who connected loses in misère mode.
+            GameResult::Winner if self.options.misere => {
+                self.data.turn = self.next_player(self.data.turn)
+            }
+            _ => (),
+        }
+        self.debug_assert_heights();
+    }
+
+    /// Return the player who moves after `player`.
+    fn next_player(&self, player: u8) -> u8 {
+        (player + 1) % self.options.players
+    }
+
+    /// Drop a `state` stone into `column` and check whether this wins or
+    /// draws the game.
+    fn drop_stone(&mut self, column: u16, state: State) {
+        let pos = self.free_cell(column);
+        self.set(pos, state);
+
+        // Only scan for the line if the bitboards found one anywhere.
+        let player = state.player().expect("dropped stone without player");
+        let line = if self.options.wrap || self.has_line(player) {
+            self.line_through(pos, state)
+        } else {
+            None
+        };
+        if let Some((start, direction)) = line {
+            self.data.result = GameResult::Winner;
+            self.data.winning_line = self
+                .line_positions(start, direction)
+                .take(self.options.length.into())
+                .collect();
+        }
+
+        // A win with the last stone is no draw.
+        if self.data.result == GameResult::Ongoing && self.board_is_full() {
+            self.data.result = GameResult::Draw;
+        }
+    }
+
+    /// Search for a line through `pos` which is long enough for winning,
+    /// assuming that `pos` holds a `state` stone.
+    ///
+    /// Returns the start of the line and the direction in which it continues.
+    /// With [`GameOptions::exact`], the whole run through `pos` is measured
+    /// and must not be longer than needed.
+    fn line_through(&self, pos: Pos, state: State) -> Option<(Pos, Direction)> {
+        let length = self.options.length.into();
+        for direction in Direction::half() {
+            if self.options.wrap && self.line_cells(direction) < length {
+                // Too short lines would count cells multiple times.
+                continue;
+            }
+
+            // Number of cells next to `pos` which may belong to the run.
+            let reach = match (self.options.exact, self.options.wrap) {
+                (false, _) => length - 1,
+                (true, false) => usize::MAX,
+                (true, true) => self.line_cells(direction) - 1,
+            };
+            let forward = self
+                .line_positions(pos, direction)
+                .skip(1)
+                .take(reach)
+                .take_while(|&p| self[p] == state)
+                .count();
+            let backward = self
+                .line_positions(pos, direction.inv())
+                .skip(1)
+                .take(reach - forward)
+                .take_while(|&p| self[p] == state)
+                .count();
+
+            // Without exact lines, the run is cut off at `length`.
+            if 1 + forward + backward == length {
+                let start = self
+                    .line_positions(pos, direction.inv())
+                    .nth(backward)
+                    .unwrap();
+                return Some((start, direction));
+            }
+        }
+
+        None
+    }
+
+    /// Check whether dropping a stone of `player` into `column` would win the
+    /// game without modifying the board.
+    ///
+    /// Returns `false` for full or non-existent columns, if the game is
+    /// already over, and in misère mode.
+    #[cfg(any(feature = "mirabel", test))]
+    pub(crate) fn is_winning_move(&self, player: player_id, column: u16) -> bool {
+        !self.options.misere
+            && column < self.lanes()
+            && self.possible_move(column)
+            && self
+                .line_through(self.free_cell(column), State::from_player_id(player))
+                .is_some()
+    }
+
+    /// Count the columns into which `player` could drop a stone and
+    /// immediately win after dropping one of their stones into `column`.
+    ///
+    /// Two or more such columns make `column` a fork which the opponent
+    /// cannot block.
+    /// Returns zero if `column` does not exist or is full.
+    /// The stone is only placed temporarily, so the game is unchanged
+    /// afterwards and nothing is allocated.
+    #[cfg(test)]
+    pub(crate) fn count_winning_replies(&mut self, player: player_id, column: u16) -> u8 {
+        if column >= self.lanes() || !self.possible_move(column) {
+            return 0;
+        }
+
+        let pos = self.free_cell(column);
+        self.set(pos, State::from_player_id(player));
+        let count = (0..self.lanes())
+            .filter(|&reply| self.is_winning_move(player, reply))
+            .count();
+        self.set(pos, State::Empty);
+        count.try_into().unwrap_or(u8::MAX)
+    }
+
+    /// Statically score the current position from the perspective of
+    /// `player`.
+    ///
+    /// Decided games score [`WIN_SCORE`] (or its negation for a loss).
+    /// Otherwise, every window of `length` fields containing stones of only
+    /// one player adds (or subtracts for opponents) 1 for two stones and 4
+    /// for a single stone missing.
+    #[cfg(test)]
+    pub(crate) fn evaluate(&self, player: player_id) -> i32 {
+        let own = player_from_id(player);
+        match self.data.result {
+            GameResult::Ongoing => (),
+            GameResult::Winner if self.data.turn == own => return WIN_SCORE,
+            GameResult::Winner => return -WIN_SCORE,
+            GameResult::Draw => return 0,
+        }
+
+        let length = self.options.length;
+        let mut score = 0;
+        for x in 0..self.options.width {
+            for y in 0..self.options.height {
+                for direction in Direction::half() {
+                    let mut fields = 0;
+                    let mut counts = [0; MAX_PLAYERS as usize];
+                    for pos in self.line_positions((x, y), direction).take(length.into()) {
+                        let state = self[pos];
+                        if state == State::Blocked {
+                            // Lines cannot pass through blocked cells.
+                            break;
+                        }
+                        if let Some(player) = state.player() {
+                            counts[usize::from(player)] += 1;
+                        }
+                        fields += 1;
+                    }
+                    let mut owners = (0..).zip(counts).filter(|&(_, count)| count != 0);
+                    let (Some((owner, count)), None) = (owners.next(), owners.next()) else {
+                        continue;
+                    };
+                    if fields < length {
+                        continue;
+                    }
+
+                    let weight = window_weight(count, length);
+                    score += if owner == own { weight } else { -weight };
+                }
+            }
+        }
+
+        score
+    }
+
+    /// Pop the bottom stone of `column` for `player` and check whether this
+    /// wins the game for any player.
+    ///
+    /// If several players get a line, `player` wins or otherwise the first
+    /// of them in turn order after `player`.
+    fn pop_stone(&mut self, column: u16, player: u8) {
+        let bottom = self.lane_cell(column, 0);
+        self.shift(bottom, self.options.gravity.inv(), State::Empty);
+
+        if let Some((winner, line)) = self.scan_for_win_from(player) {
+            self.data.result = GameResult::Winner;
+            self.data.turn = player_from_id(winner);
+            self.data.winning_line = line;
+        }
+    }
+
+    /// Scan the whole board for a line which is long enough for winning.
+    ///
+    /// Returns the owner of the line and its cells.
+    /// If several players have a line, the first of them in turn order
+    /// starting with `X` is returned.
+    /// Unlike the check after a move, this finds lines anywhere, which is
+    /// needed after popping and for validating imported states.
+    #[cfg(test)]
+    pub(crate) fn scan_for_win(&self) -> Option<(player_id, Vec<Pos>)> {
+        self.scan_for_win_from(0)
+    }
+
+    /// Same as `Self::scan_for_win()` but checks the players in turn order
+    /// starting with `first`.
+    fn scan_for_win_from(&self, first: u8) -> Option<(player_id, Vec<Pos>)> {
+        let players = self.options.players;
+        (0..players)
+            .map(|i| (first + i) % players)
+            .find_map(|player| {
+                let line = self.find_line(State::from_turn(player))?;
+                Some((player_to_id(player), line))
+            })
+    }
+
+    /// Search the whole board for a line of `state` stones which is long
+    /// enough for winning.
+    fn find_line(&self, state: State) -> Option<Vec<Pos>> {
+        let length = self.options.length.into();
+        let (width, height) = (self.options.width, self.options.height);
+        let mut cells = (0..width)
+            .flat_map(|x| (0..height).map(move |y| (x, y)))
+            .filter(|&pos| self[pos] == state);
+        if self.options.exact {
+            // Only whole runs tell whether a line is too long.
+            return cells
+                .find_map(|pos| self.line_through(pos, state))
+                .map(|(pos, direction)| {
+                    self.line_positions(pos, direction).take(length).collect()
+                });
+        }
+        cells
+            .flat_map(|pos| Direction::half().map(|direction| (pos, direction)))
+            .find(|&(pos, direction)| {
+                self.line_positions(pos, direction)
+                    .take(length)
+                    .take_while(|&p| self[p] == state)
+                    .count()
+                    == length
+            })
+            .map(|(pos, direction)| self.line_positions(pos, direction).take(length).collect())
+    }
+
+    /// Start a new game with the same options in place.
+    ///
+    /// Unlike [`GameMethods::import_state()`] with no state, this skips all
+    /// parsing and keeps every allocation, which makes it cheap for playouts.
+    #[cfg(test)]
+    pub(crate) fn restart(&mut self) {
+        self.reset_data();
+        self.place_handicap()
+            .expect("handicap stones were valid on creation");
+    }
+
+    /// Reset [`Self::data`] to an empty board on which
+    /// [`GameOptions::first_player`] is to move.
+    fn reset_data(&mut self) {
+        self.data.reset();
+        self.data.turn = self.options.first_player;
+    }
+
+    /// Drop the [`GameOptions::handicap`] stones of `O` into the columns from
+    /// the center outwards, filling one row after another.
+    ///
+    /// Like stones placed by [`GameMethods::import_state()`], they count as
+    /// moves and cannot be undone.
+    /// `X` still moves first.
+    ///
+    /// # Errors
+    /// Fails with [`InvalidOptions`] if the stones form a winning line.
+    fn place_handicap(&mut self) -> Result<()> {
+        let lanes = self.lanes();
+        let columns = iter::repeat_with(|| center_out(lanes)).flatten();
+        for column in columns.take(self.options.handicap.into()) {
+            self.set(self.free_cell(column), State::O);
+            self.data.moves += 1;
+        }
+
+        if self.find_line(State::O).is_some() {
+            return Err(Error::new_static(
+                InvalidOptions,
+                "handicap stones already form a line\0",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Return the player whose stones are written as `c` (case-insensitive).
+    ///
+    /// Returns [`None`] for characters of players not taking part.
+    fn player_of_char(&self, c: char) -> Option<u8> {
+        (0..self.options.players).find(|&p| PLAYER_CHARS[usize::from(p)].eq_ignore_ascii_case(&c))
+    }
+
+    /// Return the position of the cell in which a stone dropped into
+    /// `column` comes to rest.
+    ///
+    /// This is the free cell on top of the topmost stone or blocked cell.
+    ///
+    /// # Panics
+    /// Panics if there is no such free cell.
+    pub(crate) fn free_cell(&self, column: u16) -> Pos {
+        self.landing_cell(column).expect("move impossible")
+    }
+
+    /// Same as [`Self::free_cell()`] but returns [`None`] if `column` does
+    /// not exist or is full.
+    ///
+    /// This is independent of the game result.
+    pub(crate) fn landing_cell(&self, column: u16) -> Option<Pos> {
+        let height = *self.data.heights.get(usize::from(column))?;
+        (height < self.lane_depth()).then(|| self.lane_cell(column, height))
+    }
+}
+
+impl GameMethods for ConnectFour {
+    type Move = MoveCode;
+
+    /// Creates a new instance of the game.
+    ///
+    /// See [`GameOptions::new()`] for a documentation of the options string.
+    /// See [`Self::import_state()`] for a documentation of the state string.
+    /// See [`Self::export_serialized()`] for a documentation of the
+    /// serialized format.
+    fn create(init_info: &GameInit) -> Result<Self> {
+        Self::create_variant(init_info, false)
+    }
+
+    fn export_options(&mut self, _player: player_id, str_buf: &mut ValidCString) -> Result<()> {
+        const ERROR: &str = "writing options buffer failed";
+
+        write!(
+            str_buf,
+            "{}x{}@{}",
+            self.options.width, self.options.height, self.options.length,
+        )
+        .expect(ERROR);
+        if self.options.players != 2 {
+            write!(str_buf, "p{}", self.options.players).expect(ERROR);
+        }
+        if self.options.handicap != 0 {
+            write!(str_buf, "+h{}", self.options.handicap).expect(ERROR);
+        }
+        write!(
+            str_buf,
+            "{}{}{}{}{}",
+            self.options.gravity_symbol(),
+            if self.options.misere { "!" } else { "" },
+            if self.options.wrap { "~" } else { "" },
+            if self.options.exact { "=" } else { "" },
+            if self.options.one_based { "#" } else { "" }
+        )
+        .expect(ERROR);
+        if self.options.first_player != 0 {
+            let player = PLAYER_CHARS[usize::from(self.options.first_player)];
+            write!(str_buf, "{}", player.to_ascii_lowercase()).expect(ERROR);
+        }
+
+        Ok(())
+    }
+
+    fn copy_from(&mut self, other: &mut Self) -> Result<()> {
+        debug_assert_eq!(self.options, other.options, "options mismatch in copy_from");
+        self.data.copy_from(&other.data);
+
+        Ok(())
+    }
+
+    /// Imports state in the following format:
+    ///
+    /// ```text
+    /// XOOXXXO/XOOX//OXXO#x
+    /// ```
+    ///
+    /// Each sequence of `X`s and `O`s between `/`s represents a column of
+    /// stones from bottom to top.
+    /// The stones of a third and fourth player are `Z`s and `Y`s.
+    /// With a different gravity, each sequence represents a column (or row)
+    /// of stones starting from the edge towards which the stones fall.
+    /// A hashtag-separated, lower-case letter at the end indicates who plays
+    /// next.
+    /// An upper-case letter indicates that this player has won.
+    /// A dash indicates a draw.
+    /// An optional `@` and column index at the very end (e.g., `#x@3`) name
+    /// the column of the last move (see `ConnectFour::last_column()`).
+    /// An optional move number followed by a colon at the very beginning
+    /// (e.g., `12:XO/O//#o`) sets `ConnectFour::ply_count()`.
+    /// [`Self::export_state()`] writes neither of them.
+    ///
+    /// The state is not required to have a plausible ratio between `X`s and
+    /// `O`s and the winning player is not required to actually have a large
+    /// enough streak.
+    fn import_state(&mut self, string: Option<&str>) -> Result<()> {
+        self.reset_data();
+        let string = match string {
+            Some(s) => s.trim_start(),
+            None => {
+                return self.place_handicap();
+            }
+        };
+        let (moves, mut string) = match string.split_once(':') {
+            Some((moves, board)) => (
+                Some(parse("move number", Some(moves.trim()))?),
+                board.chars(),
+            ),
+            None => (None, string.chars()),
+        };
+
+        let mut pos = (0, 0);
+        for character in &mut string {
+            if character == '#' {
+                break;
+            }
+            if character.is_ascii_whitespace() {
+                continue;
+            }
+
+            if character == '/' {
+                pos.0 += 1;
+                pos.1 = 0;
+                if pos.0 >= self.lanes() {
+                    return Err(ErrorKind::TooManyColumns.error("state has too many columns"));
+                }
+                continue;
+            }
+
+            if pos.1 >= self.lane_depth() {
+                return Err(ErrorKind::TooManyRows.error("state has too many rows"));
+            }
+
+            let state = match State::from_char(character) {
+                Some(state) if state.player().is_none_or(|p| p < self.options.players) => state,
+                _ => return Err(player_string_error(character)),
+            };
+            self.set(self.lane_cell(pos.0, pos.1), state);
+
+            pos.1 += 1;
+            if state.player().is_some() {
+                self.data.moves += 1;
+            }
+        }
+
+        let (player, last) = match string.as_str().split_once('@') {
+            Some((player, last)) => (player.trim(), Some(last)),
+            None => (string.as_str().trim(), None),
+        };
+        if let Some(last) = last {
+            let column = parse("last move", Some(last.trim()))?;
+            if column >= self.lanes() {
+                return Err(ErrorKind::NoSuchColumn.error("last move column does not exist"));
+            }
+            // Popping may empty the column.
+            if self.data.heights[usize::from(column)] == 0 && !self.options.pop_out {
+                return Err(ErrorKind::ColumnEmpty.error("last move column is empty"));
+            }
+            self.data.imported_last = Some(column);
+        }
+
+        let mut chars = player.chars();
+        match (chars.next(), chars.next()) {
+            (Some('-'), None) => self.data.result = GameResult::Draw,
+            (Some(c), None) => {
+                self.data.turn = self
+                    .player_of_char(c)
+                    .ok_or_else(|| player_string_error(player))?;
+            }
+            _ => return Err(player_string_error(player)),
+        }
+
+        // '-' is not uppercase.
+        if player.chars().all(char::is_uppercase) {
+            self.data.result = GameResult::Winner;
+        }
+        if let Some(moves) = moves {
+            self.data.moves = moves;
+        }
+        self.debug_assert_heights();
+
+        Ok(())
+    }
+
+    fn export_state(&mut self, _player: player_id, str_buf: &mut ValidCString) -> Result<()> {
+        const ERROR: &str = "writing state buffer failed";
+
+        for lane in 0..self.lanes() {
+            if lane != 0 {
+                write!(str_buf, "/").expect(ERROR);
+            }
+            let used = (0..self.lane_depth())
+                .rev()
+                .find(|&depth| self[self.lane_cell(lane, depth)] != State::Empty)
+                .map_or(0, |depth| depth + 1);
+            for depth in 0..used {
+                write!(
+                    str_buf,
+                    "{}",
+                    match self[self.lane_cell(lane, depth)] {
+                        State::Empty => '.',
+                        State::Blocked => '*',
+                        state => state.to_char(),
+                    }
+                )
+                .expect(ERROR);
+            }
+        }
+        write!(str_buf, "#").expect(ERROR);
+
+        let player = PLAYER_CHARS[usize::from(self.data.turn)];
+        write!(
+            str_buf,
+            "{}",
+            match self.data.result {
+                GameResult::Ongoing => player.to_ascii_lowercase(),
+                GameResult::Winner => player,
+                GameResult::Draw => '-',
+            }
+        )
+        .expect(ERROR);
+
+        Ok(())
+    }
+
+    fn player_count(&mut self) -> Result<u8> {
+        Ok(self.options.players)
+    }
+
+    fn players_to_move(&mut self, players: &mut Vec<player_id>) -> Result<()> {
+        if !self.data.result.is_over() {
+            players.push(player_to_id(self.data.turn));
+        }
+
+        Ok(())
+    }
+
+    fn get_concrete_moves(&mut self, player: player_id, moves: &mut Vec<MoveCode>) -> Result<()> {
+        if player_from_id(player) == self.data.turn {
+            self.for_each_legal_move(|code| moves.push(code.into()));
+        }
+
+        Ok(())
+    }
+
+    /// Parses a column number for dropping a stone.
+    ///
+    /// Columns may also be given as a single letter (see [`letter_column()`]).
+    /// In the _Pop Out_ variant, a column prefixed with `p` pops a stone from
+    /// this column.
+    /// There, `p` is no column letter to avoid confusion with this prefix.
+    /// Column numbers start from one if [`GameOptions::one_based`] is set.
+    fn get_move_data(&mut self, _player: player_id, string: &str) -> Result<move_code> {
+        let string = string.trim();
+        let (column, flag) = match string.strip_prefix(['p', 'P']) {
+            // Without popping, a lone `p` is the letter of a column.
+            Some("") if !self.options.pop_out => (string, 0),
+            Some(column) if self.options.pop_out => (column, POP_FLAG),
+            Some(_) => {
+                return Err(Error::new_static(
+                    InvalidInput,
+                    "popping is not allowed in this variant\0",
+                ))
+            }
+            None => (string, 0),
+        };
+
+        let column: move_code = match letter_column(column) {
+            Some(_) if self.options.pop_out && column.eq_ignore_ascii_case("p") => {
+                return Err(Error::new_static(
+                    InvalidInput,
+                    "\"p\" only marks pops in this variant\0",
+                ))
+            }
+            Some(column) => column.into(),
+            None => {
+                let column: move_code = column.parse().map_err(|e| {
+                    Error::new_dynamic(InvalidInput, format!("failed to parse move: {e}"))
+                })?;
+                if !self.options.one_based {
+                    column
+                } else {
+                    column.checked_sub(1).ok_or_else(|| {
+                        ErrorKind::NoSuchColumn.error("columns are numbered from 1")
+                    })?
+                }
+            }
+        };
+        if column & POP_FLAG != 0 {
+            return Err(Error::new_static(InvalidInput, "column too large\0"));
+        }
+
+        Ok(column | flag)
+    }
+
+    fn get_move_str(
+        &mut self,
+        _player: player_id,
+        mov: MoveDataSync<&move_code>,
+        str_buf: &mut ValidCString,
+    ) -> Result<()> {
+        const ERROR: &str = "writing move buffer failed";
+
+        match Action::from_code(*mov.md) {
+            Some(Action::Drop(column)) => {
+                write!(str_buf, "{}", self.options.column_label(column))
+            }
+            Some(Action::Pop(column)) => {
+                write!(str_buf, "p{}", self.options.column_label(column))
+            }
+            None => write!(str_buf, "{}", mov.md),
+        }
+        .expect(ERROR);
+        Ok(())
+    }
+
+    fn make_move(&mut self, player: player_id, mov: MoveDataSync<&move_code>) -> Result<()> {
+        let action = Action::from_code(*mov.md).expect("column does not exist");
+        self.play(player, action);
+
+        Ok(())
+    }
+
+    /// Returns the winner or nothing for ongoing games and draws.
+    ///
+    /// Following _surena_, a game is over when
+    /// [`GameMethods::players_to_move()`] returns nobody, so an empty result
+    /// then means a draw (see also `ConnectFour::is_terminal()`).
+    fn get_results(&mut self, players: &mut Vec<player_id>) -> Result<()> {
+        if let GameResult::Winner = self.data.result {
+            players.push(player_to_id(self.data.turn));
+        }
+
+        Ok(())
+    }
+
+    fn is_legal_move(&mut self, player: player_id, mov: MoveDataSync<&move_code>) -> Result<()> {
+        // Assert unsigned type
+        assert_eq!(0, move_code::MIN);
+
+        if self.is_legal(player, *mov.md) {
+            return Ok(());
+        }
+
+        // Only describe why the move is illegal.
+        let action = Action::from_code(*mov.md).filter(|a| a.column() < self.lanes());
+        let Some(action) = action else {
+            return Err(ErrorKind::NoSuchColumn.error("column does not exist"));
+        };
+        if self.data.result.is_over() {
+            return Err(ErrorKind::GameOver.error("game is already over"));
+        }
+        if self.data.turn != player_from_id(player) {
+            return Err(ErrorKind::WrongTurn.error("not this player's turn"));
+        }
+
+        Err(match action {
+            Action::Drop(_) => ErrorKind::ColumnFull.error("column full"),
+            Action::Pop(_) if !self.options.pop_out => {
+                ErrorKind::PopNotAllowed.error("popping is not allowed in this variant")
+            }
+            Action::Pop(column) => match self[self.lane_cell(column, 0)] {
+                State::Empty => ErrorKind::ColumnEmpty.error("column empty"),
+                _ => ErrorKind::ForeignStone.error("cannot pop a stone of the opponent"),
+            },
+        })
+    }
+
+    fn print(&mut self, _player: player_id, str_buf: &mut ValidCString) -> Result<()> {
+        self.write_board(str_buf)
+            .expect("writing print buffer failed");
+
+        Ok(())
+    }
+}
+
+/// _Pop Out_ variant of [`ConnectFour`].
+///
+/// Additionally to dropping stones, players can pop one of their own stones
+/// from the bottom of a column.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub(crate) struct PopOut(ConnectFour);
+
+impl GameMethods for PopOut {
+    type Move = MoveCode;
+
+    /// Same as [`ConnectFour::create()`] but with popping enabled.
+    fn create(init_info: &GameInit) -> Result<Self> {
+        ConnectFour::create_variant(init_info, true).map(Self)
+    }
+
+    fn export_options(&mut self, player: player_id, str_buf: &mut ValidCString) -> Result<()> {
+        self.0.export_options(player, str_buf)
+    }
+
+    fn copy_from(&mut self, other: &mut Self) -> Result<()> {
+        self.0.copy_from(&mut other.0)
+    }
+
+    fn import_state(&mut self, string: Option<&str>) -> Result<()> {
+        self.0.import_state(string)
+    }
+
+    fn export_state(&mut self, player: player_id, str_buf: &mut ValidCString) -> Result<()> {
+        self.0.export_state(player, str_buf)
+    }
+
+    fn player_count(&mut self) -> Result<u8> {
+        self.0.player_count()
+    }
+
+    fn players_to_move(&mut self, players: &mut Vec<player_id>) -> Result<()> {
+        self.0.players_to_move(players)
+    }
+
+    fn get_concrete_moves(&mut self, player: player_id, moves: &mut Vec<MoveCode>) -> Result<()> {
+        self.0.get_concrete_moves(player, moves)
+    }
+
+    fn get_move_data(&mut self, player: player_id, string: &str) -> Result<move_code> {
+        self.0.get_move_data(player, string)
+    }
+
+    fn get_move_str(
+        &mut self,
+        player: player_id,
+        mov: MoveDataSync<&move_code>,
+        str_buf: &mut ValidCString,
+    ) -> Result<()> {
+        self.0.get_move_str(player, mov, str_buf)
+    }
+
+    fn make_move(&mut self, player: player_id, mov: MoveDataSync<&move_code>) -> Result<()> {
+        self.0.make_move(player, mov)
+    }
+
+    fn get_results(&mut self, players: &mut Vec<player_id>) -> Result<()> {
+        self.0.get_results(players)
+    }
+
+    fn is_legal_move(&mut self, player: player_id, mov: MoveDataSync<&move_code>) -> Result<()> {
+        self.0.is_legal_move(player, mov)
+    }
+
+    fn print(&mut self, player: player_id, str_buf: &mut ValidCString) -> Result<()> {
+        self.0.print(player, str_buf)
+    }
+}
+
+impl Display for ConnectFour {
+    /// Same as [`GameMethods::print()`].
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_board(f)
+    }
+}
+
+impl Index<Pos> for ConnectFour {
+    type Output = State;
+
+    /// Return board state at `pos`.
+    fn index(&self, pos: Pos) -> &Self::Output {
+        let index = self.idx(pos);
+        let bits = (0..self.options.cell_bits())
+            .fold(0, |n, i| n | usize::from(self.data.board[index + i]) << i);
+        CELL_STATES[bits].as_ref().expect("invalid cell bits")
+    }
+}
+
+/// Column × Row
+pub(crate) type Pos = (u16, u16);
+
+/// The state of a single field of the game board.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub(crate) enum State {
+    /// No piece at this position
+    Empty,
+    /// Player 1
+    X,
+    /// Player 2
+    O,
+    /// Cell which cannot be used by any player
+    Blocked,
+    /// Player 3
+    Z,
+    /// Player 4
+    Y,
+}
+
+/// The [`State`] of a field by its bits in [`GameData::board`], the first bit
+/// being the least significant one.
+///
+/// The other combinations never occur.
+static CELL_STATES: [Option<State>; 8] = [
+    Some(State::Empty),
+    Some(State::X),
+    Some(State::Blocked),
+    Some(State::O),
+    None,
+    Some(State::Z),
+    None,
+    Some(State::Y),
+];
+
+impl State {
+    /// Convert a [`player_id`] (1 to [`MAX_PLAYERS`]) to the state of this
+    /// player's stones.
+    ///
+    /// # Panics
+    /// Panics when given other numbers.
+    fn from_player_id(player: player_id) -> Self {
+        Self::from_turn(player_from_id(player))
+    }
+
+    /// Convert a [`GameData::turn`] index to a state.
+    fn from_turn(turn: u8) -> Self {
+        match turn {
+            0 => Self::X,
+            1 => Self::O,
+            2 => Self::Z,
+            3 => Self::Y,
+            _ => unreachable!("invalid player index"),
+        }
+    }
+
+    /// Return the [`GameData::turn`] index of the player owning this stone.
+    ///
+    /// Returns [`None`] for empty and blocked cells.
+    pub(crate) fn player(self) -> Option<u8> {
+        match self {
+            Self::Empty | Self::Blocked => None,
+            Self::X => Some(0),
+            Self::O => Some(1),
+            Self::Z => Some(2),
+            Self::Y => Some(3),
+        }
+    }
+
+    /// Return the index of this state in [`CELL_STATES`].
+    fn bits(self) -> usize {
+        CELL_STATES
+            .iter()
+            .position(|&s| s == Some(self))
+            .expect("state without bits")
+    }
+
+    /// Character of the stone in state strings.
+    ///
+    /// # Panics
+    /// Panics for empty and blocked cells.
+    fn to_char(self) -> char {
+        PLAYER_CHARS[usize::from(self.player().expect("cell without stone"))]
+    }
+
+    /// Parse a cell of a state string, ignoring the case of stones.
+    ///
+    /// Empty cells are written as `.` or `_` and blocked cells as `*`.
+    fn from_char(c: char) -> Option<Self> {
+        match c {
+            '.' | '_' => Some(Self::Empty),
+            '*' => Some(Self::Blocked),
+            _ => PLAYER_CHARS
+                .iter()
+                .position(|p| p.eq_ignore_ascii_case(&c))
+                .map(|p| Self::from_turn(p as u8)),
+        }
+    }
+}
+
+impl FromStr for State {
+    type Err = Error;
+
+    /// Parse a single cell character as in [`GameMethods::import_state()`].
+    ///
+    /// All stones are accepted regardless of the number of players.
+    /// A lone space is an empty cell as well, whereas state strings skip
+    /// whitespace and thus need `.` or `_` for empty cells.
+    fn from_str(s: &str) -> Result<Self> {
+        let mut chars = s.chars();
+        match (chars.next(), chars.next()) {
+            (Some(' '), None) => Ok(Self::Empty),
+            (Some(c), None) => Self::from_char(c).ok_or_else(|| player_string_error(c)),
+            _ => Err(player_string_error(s)),
+        }
+    }
+}
+
+/// Formats of the state strings of [`ConnectFour::export_state_as()`] and
+/// [`ConnectFour::import_state_as()`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg(test)]
+pub(crate) enum StateFormat {
+    /// The format of [`GameMethods::import_state()`].
+    Plain,
+    /// Like [`Self::Plain`] but runs of equal cells and of `/`s are written
+    /// once followed by their length (e.g., `X3O2` for `XXXOO`).
+    ///
+    /// Runs of length one have no count.
+    RunLength,
+    /// Like [`Self::Plain`] but without the `/`s of trailing empty columns
+    /// (e.g., `X#x` instead of `X//////#x`).
+    ///
+    /// The importer treats missing columns as empty anyway.
+    Compact,
+}
+
+/// A decoded [`move_code`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Action {
+    /// Drop a stone into this column.
+    Drop(u16),
+    /// Pop the bottom stone of this column.
+    Pop(u16),
+}
+
+impl Action {
+    /// Decode a [`move_code`].
+    ///
+    /// Returns [`None`] if the column is out of range.
+    fn from_code(code: move_code) -> Option<Self> {
+        let column = (code & !POP_FLAG).try_into().ok()?;
+        Some(if code & POP_FLAG == 0 {
+            Self::Drop(column)
+        } else {
+            Self::Pop(column)
+        })
+    }
+
+    /// Encode as [`move_code`].
+    fn code(self) -> move_code {
+        match self {
+            Self::Drop(column) => column.into(),
+            Self::Pop(column) => move_code::from(column) | POP_FLAG,
+        }
+    }
+
+    fn column(self) -> u16 {
+        match self {
+            Self::Drop(column) | Self::Pop(column) => column,
+        }
+    }
+}
+
+/// A move recorded in [`GameData::history`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+struct Played {
+    action: Action,
+    /// Who played this move.
+    player: u8,
+}
+
+impl Display for State {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let c = match self {
+            Self::Empty => ' ',
+            Self::X => 'X',
+            Self::O => 'O',
+            Self::Z => 'Z',
+            Self::Y => 'Y',
+            Self::Blocked => '#',
+        };
+        for _ in 0..f.width().unwrap_or(1) {
+            write!(f, "{c}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Direction on the game board.
+///
+/// North is up and east is right.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub(crate) enum Direction {
+    N,
+    NE,
+    E,
+    SE,
+    S,
+    SW,
+    W,
+    NW,
+}
+
+impl Direction {
+    /// Returns the next position from `pos` in direction `self`.
+    ///
+    /// `width` and `height` are used to perform bounds checking.
+    fn walk(&self, pos: Pos, width: u16, height: u16) -> Option<Pos> {
+        let mut next = pos;
+        match self {
+            Self::N => {
+                next.1 += 1;
+                if next.1 >= height {
+                    return None;
+                }
+            }
+            Self::NE => {
+                next.0 += 1;
+                next.1 += 1;
+
+                if next.0 >= width || next.1 >= height {
+                    return None;
+                }
+            }
+            Self::E => {
+                next.0 += 1;
+
+                if next.0 >= width {
+                    return None;
+                }
+            }
+            Self::SE => {
+                next.0 += 1;
+                next.1 = next.1.checked_sub(1)?;
+
+                if next.0 >= width {
+                    return None;
+                }
+            }
+            Self::S => {
+                next.1 = next.1.checked_sub(1)?;
+            }
+            Self::SW => {
+                next.0 = next.0.checked_sub(1)?;
+                next.1 = next.1.checked_sub(1)?;
+            }
+            Self::W => {
+                next.0 = next.0.checked_sub(1)?;
+            }
+            Self::NW => {
+                next.0 = next.0.checked_sub(1)?;
+                next.1 += 1;
+
+                if next.1 >= height {
+                    return None;
+                }
+            }
+        }
+        Some(next)
+    }
+
+    /// Returns the next position from `pos` in direction `self` wrapping
+    /// around the edges of a `width` × `height` board.
+    fn walk_wrapping(&self, pos: Pos, width: u16, height: u16) -> Pos {
+        let (dx, dy) = match self {
+            Self::N => (0, 1),
+            Self::NE => (1, 1),
+            Self::E => (1, 0),
+            Self::SE => (1, -1),
+            Self::S => (0, -1),
+            Self::SW => (-1, -1),
+            Self::W => (-1, 0),
+            Self::NW => (-1, 1),
+        };
+        let step = |coordinate: u16, delta: i32, size: u16| {
+            (i32::from(coordinate) + delta).rem_euclid(size.into()) as u16
+        };
+        (step(pos.0, dx, width), step(pos.1, dy, height))
+    }
+
+    /// Returns the opposite direction.
+    const fn inv(&self) -> Self {
+        match self {
+            Self::N => Self::S,
+            Self::NE => Self::SW,
+            Self::E => Self::W,
+            Self::SE => Self::NW,
+            Self::S => Self::N,
+            Self::SW => Self::NE,
+            Self::W => Self::E,
+            Self::NW => Self::SE,
+        }
+    }
+
+    /// Returns one half of the available directions.
+    const fn half() -> [Self; 4] {
+        [Self::N, Self::NE, Self::E, Self::SE]
+    }
+
+    /// Returns all directions, starting with [`Self::half()`].
+    #[cfg(test)]
+    const fn all() -> [Self; 8] {
+        [
+            Self::N,
+            Self::NE,
+            Self::E,
+            Self::SE,
+            Self::S,
+            Self::SW,
+            Self::W,
+            Self::NW,
+        ]
+    }
+}
+
+struct DirectionIter<'g> {
+    game: &'g ConnectFour,
+    direction: Direction,
+    current: Option<Pos>,
+}
+
+impl<'g> Iterator for DirectionIter<'g> {
+    type Item = State;
+
+    /// Returns the state of the next field in the given direction.
+    fn next(&mut self) -> Option<Self::Item> {
+        let current = self.current;
+        self.current =
+            self.direction
+                .walk(current?, self.game.options.width, self.game.options.height);
+
+        current.map(|c| self.game[c])
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub(crate) struct GameOptions {
+    width: u16,
+    height: u16,
+    /// The number of successive stones needed for victory.
+    length: u16,
+    /// Can stones be popped from the bottom (_Pop Out_ variant)?
+    pop_out: bool,
+    /// Does connecting `length` stones lose instead of win?
+    misere: bool,
+    /// Do lines continue across the edges of the board?
+    ///
+    /// This only affects win detection, not gravity.
+    wrap: bool,
+    /// Do only lines of exactly `length` stones win?
+    ///
+    /// Longer lines (overlines) do not count then.
+    exact: bool,
+    /// The direction in which dropped stones fall.
+    ///
+    /// Only [`Direction::S`] (default), [`Direction::N`], [`Direction::W`],
+    /// and [`Direction::E`] are valid.
+    gravity: Direction,
+    /// The number of players taking turns (2 to [`MAX_PLAYERS`]).
+    players: u8,
+    /// Number of `O` stones placed before the first move (see
+    /// [`ConnectFour::place_handicap()`]).
+    handicap: u16,
+    /// Are columns numbered from one instead of zero for humans?
+    ///
+    /// This only affects parsing and printing, not the move codes.
+    one_based: bool,
+    /// Index of the player who moves first in a new game.
+    ///
+    /// Imported states name the player to move themselves.
+    first_player: u8,
+}
+
+impl GameOptions {
+    /// Create a new instance of game options from an option string.
+    ///
+    /// Accepts options in the following format: `7x6@4`.
+    /// The option string consists of three separate numbers: the column count,
+    /// the row count, and the minimum number of connected pieces for winning.
+    ///
+    /// A `p` followed by a number after the length sets the number of players
+    /// (default is 2, e.g., `7x6@4p3`).
+    /// A final `+h` followed by a number places this many handicap stones of
+    /// `O` on the board (e.g., `7x6@4+h2`).
+    ///
+    /// An optional suffix of `^`, `v`, `<`, or `>` sets the direction in which
+    /// the stones fall (default is down).
+    /// A `!` in the suffix enables misère mode and a `~` lets lines wrap around
+    /// the edges of the board.
+    /// A `#` in the suffix numbers the columns from one instead of zero.
+    /// With a `=` in the suffix, only lines of exactly the given length win.
+    /// A lower-case `o`, `z`, or `y` in the suffix lets this player move first
+    /// instead of `X` (e.g., `7x6@4o`).
+    fn new(options: &str) -> Result<Self> {
+        let options = options.trim();
+        let end = options
+            .rfind(|c: char| c.is_ascii_digit())
+            .map_or(0, |i| i + 1);
+        let (options, flags) = options.split_at(end);
+
+        let mut gravity = Direction::S;
+        let mut misere = false;
+        let mut wrap = false;
+        let mut exact = false;
+        let mut one_based = false;
+        let mut first_player = 0;
+        for flag in flags.chars() {
+            gravity = match flag {
+                '^' => Direction::N,
+                'v' => Direction::S,
+                '<' => Direction::W,
+                '>' => Direction::E,
+                '!' => {
+                    misere = true;
+                    continue;
+                }
+                '~' => {
+                    wrap = true;
+                    continue;
+                }
+                '=' => {
+                    exact = true;
+                    continue;
+                }
+                '#' => {
+                    one_based = true;
+                    continue;
+                }
+                'o' | 'z' | 'y' => {
+                    first_player = (0..)
+                        .zip(PLAYER_CHARS)
+                        .find_map(|(player, c)| c.eq_ignore_ascii_case(&flag).then_some(player))
+                        .expect("player character without player");
+                    continue;
+                }
+                _ => {
+                    return Err(ErrorKind::UnexpectedSuffix
+                        .error(format_args!(r#"unexpected "{flag}" after options"#)))
+                }
+            };
+        }
+
+        let (options, handicap) = match options.split_once("+h") {
+            Some((options, handicap)) => (options, parse("handicap", Some(handicap))?),
+            None => (options, 0),
+        };
+        let (options, players) = match options.split_once(['p', 'P']) {
+            Some((options, players)) => (options, parse("player count", Some(players))?),
+            None => (options, 2),
+        };
+
+        let mut numbers = options.split(|c: char| !c.is_ascii_digit());
+        let width = parse_dimension("width", numbers.next())?;
+        let height = parse_dimension("height", numbers.next())?;
+        let length = parse_dimension("length", numbers.next())?;
+        if let Some(excess) = numbers.next() {
+            return Err(ErrorKind::UnexpectedSuffix
+                .error(format_args!(r#"unexpected "{excess}" after options"#)));
+        }
+
+        Self::builder(width, height, length)
+            .gravity(gravity)
+            .misere(misere)
+            .wrap(wrap)
+            .exact(exact)
+            .players(players)
+            .handicap(handicap)
+            .one_based(one_based)
+            .first_player(first_player)
+            .build()
+    }
+
+    /// Start building options for a `width`×`height` board on which `length`
+    /// stones win without going through an option string.
+    ///
+    /// The options are validated by [`GameOptionsBuilder::build()`].
+    pub(crate) fn builder(width: u16, height: u16, length: u16) -> GameOptionsBuilder {
+        GameOptionsBuilder(Self {
+            width,
+            height,
+            length,
+            ..Default::default()
+        })
+    }
+
+    /// Check that the options are consistent and return them unchanged.
+    ///
+    /// Both [`Self::new()`] and [`GameOptionsBuilder::build()`] end here, so
+    /// they cannot disagree about which options are valid.
+    ///
+    /// # Errors
+    /// Fails with [`InvalidOptions`] if any option is out of range or
+    /// conflicts with another one.
+    fn validated(self) -> Result<Self> {
+        let (width, height, length) = (self.width, self.height, self.length);
+        if width < 1 || height < 1 || length < 1 {
+            return Err(ErrorKind::DimensionTooSmall
+                .error("width, height, and length need to be at least 1"));
+        };
+        let max = self.max_run();
+        if length > max {
+            let wrapping = if self.wrap {
+                " with wrapping lines"
+            } else {
+                ""
+            };
+            return Err(ErrorKind::LengthTooLarge.error(format_args!(
+                "length must not exceed {max}, the longest line on a \
+                 {width}x{height} board{wrapping}"
+            )));
+        }
+        if !matches!(
+            self.gravity,
+            Direction::S | Direction::N | Direction::W | Direction::E
+        ) {
+            return Err(Error::new_static(InvalidOptions, "invalid gravity\0"));
+        }
+
+        self.check_players()?.check_handicap()
+    }
+
+    /// Check that [`Self::players`] is in range and compatible with the
+    /// other options.
+    fn check_players(self) -> Result<Self> {
+        if !(2..=MAX_PLAYERS).contains(&self.players) {
+            return Err(ErrorKind::PlayerCount.error(format_args!(
+                "player count must be between 2 and {MAX_PLAYERS}"
+            )));
+        }
+        if self.misere && self.players != 2 {
+            return Err(ErrorKind::PlayerCount.error("misère mode needs exactly two players"));
+        }
+        if self.first_player >= self.players {
+            return Err(ErrorKind::PlayerCount.error("first player does not take part"));
+        }
+
+        Ok(self)
+    }
+
+    /// Check that the [`Self::handicap`] stones fit onto the board.
+    fn check_handicap(self) -> Result<Self> {
+        if usize::from(self.handicap) > usize::from(self.width) * usize::from(self.height) {
+            return Err(ErrorKind::TooManyHandicap.error("too many handicap stones"));
+        }
+
+        Ok(self)
+    }
+
+    /// Number of bits per field in [`GameData::board`].
+    ///
+    /// The third bit is only needed for the stones of more than two players.
+    fn cell_bits(&self) -> usize {
+        if self.players > 2 {
+            3
+        } else {
+            2
+        }
+    }
+
+    /// Length of the longest line which fits on the board.
+    ///
+    /// Rows hold [`Self::width`] and columns [`Self::height`] stones while
+    /// diagonals never exceed the shorter of both.
+    /// With [`Self::wrap`], diagonals continue across the edges until they
+    /// return to their start, which takes the least common multiple of both
+    /// dimensions (see [`ConnectFour::line_cells()`]).
+    /// This is never shorter than rows and columns.
+    fn max_run(&self) -> u16 {
+        let (width, height) = (usize::from(self.width), usize::from(self.height));
+        if self.wrap {
+            (width / gcd(width, height) * height)
+                .try_into()
+                .unwrap_or(u16::MAX)
+        } else {
+            self.width.max(self.height)
+        }
+    }
+
+    /// Encode the options which are not numbers into a byte.
+    ///
+    /// The two least significant bits encode the gravity: `0` → down, `1` →
+    /// up, `2` → left, and `3` → right.
+    /// The next bits are set in misère mode and when lines wrap around.
+    /// The two bits after them store the number of players minus two.
+    /// The seventh bit is set if columns are numbered from one and the
+    /// eighth bit if only lines of exactly the given length win.
+    fn flags(&self) -> u8 {
+        let gravity = match self.gravity {
+            Direction::S => 0,
+            Direction::N => 1,
+            Direction::W => 2,
+            Direction::E => 3,
+            _ => unreachable!("invalid gravity"),
+        };
+        gravity
+            | u8::from(self.misere) << 2
+            | u8::from(self.wrap) << 3
+            | (self.players - 2) << 4
+            | u8::from(self.one_based) << 6
+            | u8::from(self.exact) << 7
+    }
+
+    /// Set the options encoded by [`Self::flags()`].
+    ///
+    /// The result still needs to be checked by [`Self::validated()`].
+    fn with_flags(self, flags: u8) -> Self {
+        let gravity = match flags & 0b11 {
+            0 => Direction::S,
+            1 => Direction::N,
+            2 => Direction::W,
+            3 => Direction::E,
+            _ => unreachable!(),
+        };
+        Self {
+            gravity,
+            misere: flags & 0b100 != 0,
+            wrap: flags & 0b1000 != 0,
+            players: 2 + (flags >> 4 & 0b11),
+            one_based: flags & 0b100_0000 != 0,
+            exact: flags & 0b1000_0000 != 0,
+            ..self
+        }
+    }
+
+    /// See [`ConnectFour::lanes()`].
+    fn lanes(&self) -> u16 {
+        match self.gravity {
+            Direction::N | Direction::S => self.width,
+            _ => self.height,
+        }
+    }
+
+    /// Option string suffix for the gravity.
+    fn gravity_symbol(&self) -> &'static str {
+        match self.gravity {
+            Direction::S => "",
+            Direction::N => "^",
+            Direction::W => "<",
+            Direction::E => ">",
+            _ => unreachable!("invalid gravity"),
+        }
+    }
+
+    /// Do stones fall down like in regular _Connect Four_?
+    #[cfg(feature = "mirabel")]
+    pub(crate) fn gravity_is_down(&self) -> bool {
+        self.gravity == Direction::S
+    }
+
+    /// Number of character required to print the largest column label.
+    ///
+    /// See [`Self::column_label()`].
+    fn col_chars(&self) -> usize {
+        match self.column_label(self.width.saturating_sub(1)) {
+            0..=9 => 1,
+            10..=99 => 2,
+            100..=999 => 3,
+            1000..=9999 => 4,
+            _ => 5,
+        }
+    }
+
+    /// Number shown to humans for the column with index `column`.
+    ///
+    /// Column indices start from zero but labels from one if
+    /// [`Self::one_based`] is set.
+    fn column_label(&self, column: u16) -> u32 {
+        u32::from(column) + u32::from(self.one_based)
+    }
+
+    /// Width of the board.
+    #[cfg(any(feature = "mirabel", feature = "export"))]
+    pub(crate) fn width(&self) -> u16 {
+        self.width
+    }
+
+    /// Height of the board.
+    #[cfg(any(feature = "mirabel", feature = "export"))]
+    pub(crate) fn height(&self) -> u16 {
+        self.height
+    }
+}
+
+/// Builder for [`GameOptions`] created by [`GameOptions::builder()`].
+///
+/// Options which are not set keep their defaults.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct GameOptionsBuilder(GameOptions);
+
+impl GameOptionsBuilder {
+    /// See [`GameOptions::misere`].
+    pub(crate) fn misere(mut self, misere: bool) -> Self {
+        self.0.misere = misere;
+        self
+    }
+
+    /// See [`GameOptions::wrap`].
+    pub(crate) fn wrap(mut self, wrap: bool) -> Self {
+        self.0.wrap = wrap;
+        self
+    }
+
+    /// See [`GameOptions::exact`].
+    pub(crate) fn exact(mut self, exact: bool) -> Self {
+        self.0.exact = exact;
+        self
+    }
+
+    /// See [`GameOptions::gravity`].
+    pub(crate) fn gravity(mut self, gravity: Direction) -> Self {
+        self.0.gravity = gravity;
+        self
+    }
+
+    /// See [`GameOptions::players`].
+    pub(crate) fn players(mut self, players: u8) -> Self {
+        self.0.players = players;
+        self
+    }
+
+    /// See [`GameOptions::handicap`].
+    pub(crate) fn handicap(mut self, handicap: u16) -> Self {
+        self.0.handicap = handicap;
+        self
+    }
+
+    /// See [`GameOptions::first_player`].
+    pub(crate) fn first_player(mut self, first_player: u8) -> Self {
+        self.0.first_player = first_player;
+        self
+    }
+
+    /// See [`GameOptions::one_based`].
+    pub(crate) fn one_based(mut self, one_based: bool) -> Self {
+        self.0.one_based = one_based;
+        self
+    }
+
+    /// Validate the options like [`GameOptions::new()`] does.
+    ///
+    /// # Errors
+    /// Fails with the same errors as [`GameOptions::new()`] and with
+    /// [`InvalidOptions`] for an invalid gravity.
+    pub(crate) fn build(self) -> Result<GameOptions> {
+        self.0.validated()
+    }
+}
+
+impl Default for GameOptions {
+    fn default() -> Self {
+        Self {
+            width: DEFAULT_WIDTH,
+            height: DEFAULT_HEIGHT,
+            length: DEFAULT_LENGTH,
+            pop_out: false,
+            misere: false,
+            wrap: false,
+            exact: false,
+            gravity: Direction::S,
+            players: 2,
+            handicap: 0,
+            one_based: false,
+            first_player: 0,
+        }
+    }
+}
+
+impl FromStr for GameOptions {
+    type Err = Error;
+
+    /// Same as [`GameOptions::new()`].
+    fn from_str(s: &str) -> Result<Self> {
+        Self::new(s)
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+struct GameData {
+    /// Every two bits describe a single field.
+    ///
+    /// The first of these bits signals if the field is even occupied.
+    /// The second one signals the piece color if occupied.
+    /// If only the second bit is set, the field is blocked.
+    /// With more than two players, a third bit per field adds two to the
+    /// player of the piece (see [`CELL_STATES`]).
+    board: Board,
+    /// Index of the player to move: `0` → `X`, `1` → `O`, `2` → `Z`, and
+    /// `3` → `Y`
+    turn: u8,
+    result: GameResult,
+    /// Cells of the line which won the game in the last move.
+    winning_line: Vec<Pos>,
+    /// All moves played since the last reset.
+    history: Vec<Played>,
+    /// Column of the last move before the [`Self::history`] as given by
+    /// [`GameMethods::import_state()`].
+    imported_last: Option<u16>,
+    /// Zobrist hash of the board only.
+    ///
+    /// `turn` and `result` are folded in by `ConnectFour::id()`.
+    hash: u64,
+    /// See `ConnectFour::ply_count()`.
+    moves: u16,
+    /// Bitboards of the stones of each player.
+    ///
+    /// Bit `x * height + y` is set if there is such a stone at `(x, y)`.
+    stones: Vec<BitVec>,
+    /// Cache of the depth of the topmost non-empty cell plus one for each
+    /// lane (see [`ConnectFour::lane_cell()`]).
+    ///
+    /// This is zero for empty lanes.
+    heights: Vec<u16>,
+}
+
+impl GameData {
+    fn new(options: &GameOptions) -> Self {
+        let cells = usize::from(options.width) * usize::from(options.height);
+        Self {
+            board: Board::new(options.cell_bits() * cells),
+            turn: 0,
+            result: GameResult::Ongoing,
+            winning_line: vec![],
+            history: vec![],
+            imported_last: None,
+            hash: 0,
+            moves: 0,
+            stones: (0..options.players).map(|_| BitVec::new(cells)).collect(),
+            heights: vec![0; usize::from(options.lanes())],
+        }
+    }
+
+    fn copy_from(&mut self, other: &Self) {
+        self.board.copy_from(&other.board);
+        self.turn = other.turn;
+        self.result = other.result;
+        self.winning_line.clone_from(&other.winning_line);
+        self.history.clone_from(&other.history);
+        self.imported_last = other.imported_last;
+        self.hash = other.hash;
+        self.moves = other.moves;
+        for (stones, other) in self.stones.iter_mut().zip(&other.stones) {
+            stones.copy_from_bitvec(other);
+        }
+        self.heights.copy_from_slice(&other.heights);
+    }
+
+    /// Same as [`Self::copy_from()`] but `other` may belong to a game with
+    /// different options.
+    ///
+    /// Reuses the existing allocations where possible.
+    #[cfg(test)]
+    fn copy_resized_from(&mut self, other: &Self) {
+        self.board.copy_resized_from(&other.board);
+        self.turn = other.turn;
+        self.result = other.result;
+        self.winning_line.clone_from(&other.winning_line);
+        self.history.clone_from(&other.history);
+        self.imported_last = other.imported_last;
+        self.hash = other.hash;
+        self.moves = other.moves;
+        self.stones.truncate(other.stones.len());
+        for (stones, other) in self.stones.iter_mut().zip(&other.stones) {
+            stones.copy_resized_from(other);
+        }
+        let start = self.stones.len();
+        self.stones.extend_from_slice(&other.stones[start..]);
+        self.heights.clone_from(&other.heights);
+    }
+
+    fn reset(&mut self) {
+        self.board.reset();
+        self.turn = 0;
+        self.result = GameResult::Ongoing;
+        self.winning_line.clear();
+        self.history.clear();
+        self.imported_last = None;
+        self.hash = 0;
+        self.moves = 0;
+        for stones in &mut self.stones {
+            stones.reset();
+        }
+        self.heights.fill(0);
+    }
+}
+
+/// Serializable view of a [`ConnectFour`] game.
+///
+/// Like [`ConnectFour::export_serialized()`], this drops the move history.
+#[cfg(feature = "serde")]
+#[derive(Serialize, Deserialize)]
+struct SerdeGame {
+    options: GameOptions,
+    /// [`GameData::board`] packed by [`BitVec::to_bytes()`].
+    board: Vec<u8>,
+    turn: u8,
+    result: GameResult,
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for ConnectFour {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        SerdeGame {
+            options: self.options,
+            board: self.data.board.to_bytes(),
+            turn: self.data.turn,
+            result: self.data.result,
+        }
+        .serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for ConnectFour {
+    /// Validates the options and the board like
+    /// [`ConnectFour::import_serialized()`].
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let game = SerdeGame::deserialize(deserializer)?;
+        game.options
+            .validated()
+            .and_then(|o| Self::from_parts(o, &game.board, game.turn, game.result))
+            .map_err(|e| de::Error::custom(format_args!("invalid game ({:?})", e.code)))
+    }
+}
+
+/// Random numbers for Zobrist hashing.
+///
+/// The table is built once when a game is created.
+/// Its numbers are drawn from a generator seeded with [`ZOBRIST_SEED`], so
+/// ids are stable across runs.
+#[derive(Clone, PartialEq, Eq)]
+struct Zobrist {
+    /// Numbers for every non-empty [`State`] of each cell.
+    ///
+    /// See [`Self::cell()`] for the order.
+    cells: Vec<u64>,
+    /// Number for each player to move (see [`GameData::turn`]).
+    turns: [u64; MAX_PLAYERS as usize],
+    /// Number for won games.
+    winner: u64,
+    /// Number for drawn games.
+    draw: u64,
+}
+
+impl Zobrist {
+    /// Number of non-empty [`State`]s.
+    const STATES: usize = 5;
+
+    /// Build the table for a board with the dimensions of `options`.
+    fn new(options: &GameOptions) -> Self {
+        let mut numbers = SplitMix64(ZOBRIST_SEED);
+        let turns = std::array::from_fn(|_| numbers.next());
+        let winner = numbers.next();
+        let draw = numbers.next();
+        let cells = usize::from(options.width) * usize::from(options.height);
+        Self {
+            cells: iter::repeat_with(|| numbers.next())
+                .take(Self::STATES * cells)
+                .collect(),
+            turns,
+            winner,
+            draw,
+        }
+    }
+
+    /// Return the number for `state` in the cell with the index `index` into
+    /// [`GameData::stones`].
+    ///
+    /// Empty cells have no number.
+    fn cell(&self, index: usize, state: State) -> u64 {
+        match state {
+            State::Empty => 0,
+            // The other states start from one.
+            _ => self.cells[Self::STATES * index + state as usize - 1],
+        }
+    }
+}
+
+impl fmt::Debug for Zobrist {
+    /// Omits the numbers, which only depend on the options.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Zobrist").finish_non_exhaustive()
+    }
+}
+
+/// Masks and strides for finding lines in [`GameData::stones`].
+///
+/// There is one pair for each of the vertical, horizontal, and two diagonal
+/// orientations.
+/// Moving one cell in an orientation adds its stride to the bit index.
+/// A mask bit is set if a line in this orientation starting at this cell fits
+/// onto the board, which rules out lines wrapping from one column into the
+/// next.
+#[derive(Clone, PartialEq, Eq, Debug)]
+struct LineMasks([(BitVec, usize); 4]);
+
+impl LineMasks {
+    /// Build the masks for a board with the dimensions of `options`.
+    fn new(options: &GameOptions) -> Self {
+        let (width, height) = (usize::from(options.width), usize::from(options.height));
+        let reach = usize::from(options.length) - 1;
+        // `end` returns the last cell of the line starting at a cell.
+        let mask = |end: &dyn Fn(usize, usize) -> Option<(usize, usize)>| {
+            (0..width)
+                .flat_map(|x| (0..height).map(move |y| (x, y)))
+                .map(|(x, y)| matches!(end(x, y), Some((x, y)) if x < width && y < height))
+                .collect()
+        };
+
+        Self([
+            (mask(&|x, y| Some((x, y + reach))), 1),
+            (mask(&|x, y| Some((x + reach, y))), height),
+            (mask(&|x, y| Some((x + reach, y + reach))), height + 1),
+            (
+                mask(&|x, y| Some((x + reach, y.checked_sub(reach)?))),
+                height - 1,
+            ),
+        ])
+    }
+}
+
+/// Minimal _SplitMix64_ pseudo-random number generator.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
+
+/// Possible states of the game.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub(crate) enum GameResult {
+    Ongoing,
+    Winner,
+    Draw,
+}
+
+impl GameResult {
+    fn is_over(&self) -> bool {
+        self != &Self::Ongoing
+    }
+}
+
+/// Weight of a window with `count` stones of a single player in
+/// [`ConnectFour::evaluate`].
+#[cfg(test)]
+const fn window_weight(count: u16, length: u16) -> i32 {
+    if count == 0 || count >= length {
+        0
+    } else if count + 1 == length {
+        4
+    } else if count >= 2 {
+        1
+    } else {
+        0
+    }
+}
+
+/// Converts a `player_id` to the [`GameData::turn`] index.
+///
+/// # Panics
+/// Panics if given numbers other than 1 to [`MAX_PLAYERS`].
+pub(crate) const fn player_from_id(player: player_id) -> u8 {
+    match player {
+        1..=MAX_PLAYERS => player - 1,
+        _ => unreachable!(),
+    }
+}
+
+/// Converts the [`GameData::turn`] index to a `player_id`.
+pub(crate) const fn player_to_id(player: u8) -> player_id {
+    player + 1
+}
+
+/// Categories of errors found while parsing and validating options, states,
+/// and moves.
+///
+/// Many of them share the same [`ErrorCode`], so every error message starts
+/// with the [`Self::tag()`] of its kind and a colon (e.g.,
+/// `too_many_rows: state has too many rows`).
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum ErrorKind {
+    /// A number is missing or malformed.
+    Number,
+    /// Unexpected characters follow the options.
+    UnexpectedSuffix,
+    /// Width, height, or length is zero.
+    DimensionTooSmall,
+    /// A dimension does not fit into a [`u16`].
+    DimensionTooLarge,
+    /// The length exceeds the longest line on the board.
+    LengthTooLarge,
+    /// The number of players is out of range or does not fit the variant.
+    PlayerCount,
+    /// The handicap stones do not fit onto the board.
+    TooManyHandicap,
+    /// A state has more columns (or rows with sideways gravity) than the
+    /// board.
+    TooManyColumns,
+    /// A column (or row) of a state has too many cells.
+    TooManyRows,
+    /// A character does not denote a player.
+    InvalidPlayer,
+    /// The column of a move does not exist.
+    NoSuchColumn,
+    /// The game has already ended.
+    GameOver,
+    /// Another player is to move.
+    WrongTurn,
+    /// The column of a dropping move is full.
+    ColumnFull,
+    /// The column of a popping move or the last move is empty.
+    ColumnEmpty,
+    /// Popping is not allowed in this variant.
+    PopNotAllowed,
+    /// The stone to pop belongs to another player.
+    ForeignStone,
+    /// A won game has no line which is long enough for winning.
+    #[cfg(test)]
+    MissingLine,
+    /// A game without winner has a line which is long enough for winning.
+    #[cfg(test)]
+    UnexpectedLine,
+}
+
+impl ErrorKind {
+    /// Return the [`ErrorCode`] reported for this kind.
+    fn code(self) -> ErrorCode {
+        match self {
+            Self::DimensionTooSmall
+            | Self::DimensionTooLarge
+            | Self::LengthTooLarge
+            | Self::PlayerCount
+            | Self::TooManyHandicap => InvalidOptions,
+            _ => InvalidInput,
+        }
+    }
+
+    /// Return the prefix of messages of this kind.
+    pub(crate) fn tag(self) -> &'static str {
+        match self {
+            Self::Number => "number",
+            Self::UnexpectedSuffix => "unexpected_suffix",
+            Self::DimensionTooSmall => "dimension_too_small",
+            Self::DimensionTooLarge => "dimension_too_large",
+            Self::LengthTooLarge => "length_too_large",
+            Self::PlayerCount => "player_count",
+            Self::TooManyHandicap => "too_many_handicap",
+            Self::TooManyColumns => "too_many_columns",
+            Self::TooManyRows => "too_many_rows",
+            Self::InvalidPlayer => "invalid_player",
+            Self::NoSuchColumn => "no_such_column",
+            Self::GameOver => "game_over",
+            Self::WrongTurn => "wrong_turn",
+            Self::ColumnFull => "column_full",
+            Self::ColumnEmpty => "column_empty",
+            Self::PopNotAllowed => "pop_not_allowed",
+            Self::ForeignStone => "foreign_stone",
+            #[cfg(test)]
+            Self::MissingLine => "missing_line",
+            #[cfg(test)]
+            Self::UnexpectedLine => "unexpected_line",
+        }
+    }
+
+    /// Create an error of this kind with the [`Self::tag()`] prepended to
+    /// `message`.
+    fn error(self, message: impl Display) -> Error {
+        Error::new_dynamic(self.code(), format!("{}: {message}", self.tag()))
+    }
+}
+
+fn player_string_error(player: impl Display) -> Error {
+    ErrorKind::InvalidPlayer.error(format_args!(r#""{player}" is not a valid player"#))
+}
+
+/// Parse the supplied `string`.
+///
+/// # Errors
+/// Creates a descriptive error message using `name` if `string` is [`None`] or
+/// failed to parse.
+fn parse<D: FromStr>(name: &str, string: Option<&str>) -> Result<D>
+where
+    <D as FromStr>::Err: Display,
+{
+    string
+        .ok_or_else(|| ErrorKind::Number.error(format_args!("missing {name}")))?
+        .parse()
+        .map_err(|e| ErrorKind::Number.error(format_args!("failed to parse {name}: {e}")))
+}
+
+/// Parse a board dimension with [`parse()`] but report values which do not
+/// fit into a [`u16`] as invalid options.
+fn parse_dimension(name: &str, string: Option<&str>) -> Result<u16> {
+    if let Some(number) = string {
+        if let Err(e) = number.parse::<u16>() {
+            if *e.kind() == IntErrorKind::PosOverflow {
+                return Err(ErrorKind::DimensionTooLarge
+                    .error(format_args!("{name} must not exceed {}", u16::MAX)));
+            }
+        }
+    }
+
+    parse(name, string)
+}
+
+/// Column labeled by the single, case-insensitive letter `string` if any.
+///
+/// The letter `a` labels column 0, and `z` labels column 25.
+fn letter_column(string: &str) -> Option<u16> {
+    let mut chars = string.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if c.is_ascii_alphabetic() => {
+            Some(u16::from(c.to_ascii_lowercase() as u8 - b'a'))
+        }
+        _ => None,
+    }
+}
+
+/// Iterate through the `lanes` from the center outwards.
+///
+/// Ties are broken towards the left.
+fn center_out(lanes: u16) -> impl Iterator<Item = u16> {
+    let center = lanes / 2;
+    (0..=center).flat_map(move |distance| {
+        let right = center + distance;
+        let right = (distance != 0 && right < lanes).then_some(right);
+        iter::once(center - distance).chain(right)
+    })
+}
+
+/// Greatest common divisor
+fn gcd(mut a: usize, mut b: usize) -> usize {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+#[cfg(target_pointer_width = "16")]
+const ERROR: () = "16 bit architectures are not supported.";
+
+#[cfg(test)]
+mod tests {
+    use std::ptr::{null, null_mut};
+
+    use mirabel::{
+        error::ErrorCode::{self, InvalidInput, InvalidOptions},
+        game::{GameMethods, PLAYER_NONE},
+        MoveDataSync,
+    };
+
+    use super::*;
+
+    #[test]
+    fn get_game_methods() {
+        unsafe {
+            let mut count = 0;
+            plugin_get_game_methods(&mut count, null_mut());
+            assert_eq!(2, count);
+
+            let mut count = 0;
+            let mut methods = null();
+            plugin_get_game_methods(&mut count, &mut methods);
+            assert_eq!(2, count);
+            assert_ne!(null(), methods);
+        }
+    }
+
+    #[test]
+    fn create() {
+        let game = ConnectFour::create(&GameInit::Default).unwrap();
+        assert_eq!(DEFAULT_WIDTH, game.options.width);
+        assert_eq!(DEFAULT_HEIGHT, game.options.height);
+        assert_eq!(DEFAULT_LENGTH, game.options.length);
+
+        let game = ConnectFour::create(&GameInit::Standard {
+            opts: Some("4x3@2"),
+            legacy: None,
+            state: None,
+        })
+        .unwrap();
+
+        assert_eq!(4, game.options.width);
+        assert_eq!(3, game.options.height);
+        assert_eq!(2, game.options.length);
+
+        fn create(string: &str) -> ErrorCode {
+            ConnectFour::create(&GameInit::Standard {
+                opts: Some(string),
+                legacy: None,
+                state: None,
+            })
+            .unwrap_err()
+            .code
+        }
+        assert_eq!(InvalidInput, create(""));
+        assert_eq!(InvalidInput, create("7x4"));
+        assert_eq!(InvalidInput, create("-5x4@2"));
+        assert_eq!(InvalidOptions, create("4x4@5"));
+        assert_eq!(InvalidOptions, create("2x0@1"));
+        assert_eq!(InvalidOptions, create("2x2@3"));
+        assert_eq!(InvalidOptions, create("7x6@4p5"));
+        assert_eq!(InvalidOptions, create("7x6@4p1"));
+        assert_eq!(InvalidOptions, create("7x6@4p3!"));
+        assert_eq!(InvalidInput, create("7x6@4p"));
+        assert_eq!(InvalidOptions, create("2x2@2+h5"));
+        assert_eq!(InvalidOptions, create("3x1@4"));
+        assert!(ConnectFour::create(&GameInit::Standard {
+            opts: Some("2x2@2"),
+            legacy: None,
+            state: None,
+        })
+        .is_ok());
+    }
+
+    #[test]
+    fn gravity() {
+        for (options, expected) in [
+            ("4x3@3", [(1, 0), (1, 1), (2, 0)]),
+            ("4x3@3v", [(1, 0), (1, 1), (2, 0)]),
+            ("4x3@3^", [(1, 2), (1, 1), (2, 2)]),
+            ("4x3@3<", [(0, 1), (1, 1), (0, 2)]),
+            ("4x3@3>", [(3, 1), (2, 1), (3, 2)]),
+        ] {
+            let mut game = ConnectFour::create(&GameInit::Standard {
+                opts: Some(options),
+                legacy: None,
+                state: None,
+            })
+            .unwrap();
+            assert_eq!(expected[0], game.free_cell(1));
+            game.make_move(1, sync(&1)).unwrap();
+            assert_eq!(State::X, game[expected[0]]);
+            game.make_move(2, sync(&1)).unwrap();
+            assert_eq!(State::O, game[expected[1]]);
+            game.make_move(1, sync(&2)).unwrap();
+            assert_eq!(State::X, game[expected[2]]);
+
+            let mut storage = ValidCString::default();
+            game.export_state(PLAYER_NONE, &mut storage).unwrap();
+            let mut copy = game.clone();
+            copy.import_state(Some(storage.as_ref())).unwrap();
+            assert_eq!(game.data.board, copy.data.board);
+
+            let mut storage = ValidCString::default();
+            game.export_options(PLAYER_NONE, &mut storage).unwrap();
+            assert_eq!(game.options, GameOptions::new(storage.as_ref()).unwrap());
+
+            game.undo_move().unwrap();
+            assert_eq!(State::Empty, game[expected[2]]);
+        }
+
+        let mut game = ConnectFour::create(&GameInit::Standard {
+            opts: Some("4x3@3<"),
+            legacy: None,
+            state: Some("XOXO/X/OXXO#x"),
+        })
+        .unwrap();
+        let mut storage = vec![];
+        game.get_concrete_moves(1, &mut storage).unwrap();
+        assert_eq!([1], *MoveCode::slice_to_rust(&storage));
+        let err = game.is_legal_move(1, sync(&3)).unwrap_err().code;
+        assert_eq!(InvalidInput, err);
+        game.make_move(1, sync(&1)).unwrap();
+        assert_eq!(GameResult::Winner, game.data.result);
+        assert_eq!(
+            Some([(0, 0), (1, 1), (2, 2)].as_slice()),
+            game.winning_line()
+        );
+
+        let mut game = ConnectFour::create(&GameInit::Standard {
+            opts: Some("3x2@3^"),
+            legacy: None,
+            state: Some("X/O#x"),
+        })
+        .unwrap();
+        for (player, column) in [(1, 2), (2, 2), (1, 0), (2, 1)] {
+            game.make_move(player, sync(&column)).unwrap();
+        }
+        assert_eq!(GameResult::Draw, game.data.result);
+
+        assert_eq!(InvalidInput, GameOptions::new("7x6@4^x").unwrap_err().code);
+    }
+
+    #[test]
+    fn import_state() {
+        let mut game = create_with_state("XO/O/////X#O");
+        assert_eq!(1, game.data.turn);
+        assert_eq!(GameResult::Winner, game.data.result);
+        assert_eq!(State::X, game[(0, 0)]);
+        assert_eq!(State::O, game[(0, 1)]);
+        assert_eq!(State::O, game[(1, 0)]);
+        assert_eq!(State::Empty, game[(1, 1)]);
+        assert_eq!(State::X, game[(6, 0)]);
+
+        game.import_state(None).unwrap();
+        assert_eq!(0, game.data.turn);
+        assert_eq!(GameResult::Ongoing, game.data.result);
+        assert!(!game.data.board.any());
+        assert_eq!(
+            2 * usize::from(game.options.width) * usize::from(game.options.height),
+            game.data.board.len()
+        );
+
+        game.import_state(Some("/XO//#-")).unwrap();
+        assert_eq!(GameResult::Draw, game.data.result);
+        assert_eq!(State::X, game[(1, 0)]);
+        assert_eq!(State::O, game[(1, 1)]);
+        assert_eq!(State::Empty, game[(1, 2)]);
+
+        fn assert_invalid(game: &mut ConnectFour, string: &str) {
+            let err = game.import_state(Some(string)).unwrap_err().code;
+            assert_eq!(InvalidInput, err);
+        }
+        assert_invalid(&mut game, "///////#x");
+        assert_invalid(&mut game, "XXXXXXXXXX#-");
+        assert_invalid(&mut game, "X/O/X#F");
+        assert_invalid(&mut game, "X-O#x");
+
+        game.import_state(Some("X.O/../#x")).unwrap();
+        assert_eq!(State::X, game[(0, 0)]);
+        assert_eq!(State::Empty, game[(0, 1)]);
+        assert_eq!(State::O, game[(0, 2)]);
+        assert_eq!(State::Empty, game[(1, 0)]);
+        assert_eq!(State::Empty, game[(1, 1)]);
+        let mut storage = ValidCString::default();
+        game.export_state(PLAYER_NONE, &mut storage).unwrap();
+        assert_eq!("X.O//////#x", storage.as_ref());
+
+        game.import_state(Some("XO__/\n O X /\n\t_/\n XX\n#o"))
+            .unwrap();
+        let mut storage = ValidCString::default();
+        game.export_state(PLAYER_NONE, &mut storage).unwrap();
+        assert_eq!("XO/OX//XX///#o", storage.as_ref());
+    }
+
+    #[test]
+    fn serialized() {
+        let mut game = ConnectFour::create(&GameInit::Standard {
+            opts: Some("5x4@3"),
+            legacy: None,
+            state: None,
+        })
+        .unwrap();
+        for column in [2, 2, 3, 0, 4] {
+            let player = player_to_id(game.data.turn);
+            game.make_move(player, sync(&column)).unwrap();
+        }
+
+        let mut bytes = vec![];
+        game.export_serialized(&mut bytes);
+        assert_eq!(10 + 5 + 1, bytes.len());
+        let copy = ConnectFour::create(&GameInit::Serialized(&bytes)).unwrap();
+        assert_eq!(game.data.board, copy.data.board);
+        assert_eq!(game.data.turn, copy.data.turn);
+        assert_eq!(game.data.result, copy.data.result);
+        assert_eq!(game.id(), copy.id());
+
+        /// Serialize a game with the given options followed by `rest`.
+        fn serialized(w: u8, h: u8, l: u8, flags: u8, rest: &[u8]) -> Vec<u8> {
+            let mut bytes = vec![SERIALIZED_VERSION, w, 0, h, 0, l, 0, 0, 0, flags];
+            bytes.extend(rest);
+            bytes
+        }
+        fn create(bytes: &[u8]) -> ErrorCode {
+            ConnectFour::create(&GameInit::Serialized(bytes))
+                .unwrap_err()
+                .code
+        }
+        assert_eq!(InvalidInput, create(&[]));
+        assert_eq!(InvalidInput, create(&[SERIALIZED_VERSION, 5, 0, 4, 0, 3]));
+        assert_eq!(InvalidInput, create(&[0, 1, 0, 1, 0, 1, 0, 0, 0, 0, 0, 0]));
+        assert_eq!(InvalidOptions, create(&serialized(5, 4, 6, 0, &[0])));
+        assert_eq!(
+            InvalidOptions,
+            create(&serialized(5, 4, 3, 0b110000, &[0, 0]))
+        );
+        assert_eq!(InvalidInput, create(&bytes[..bytes.len() - 1]));
+        assert_eq!(InvalidInput, create(&serialized(1, 1, 1, 0, &[0, 0, 0])));
+        assert_eq!(InvalidInput, create(&serialized(1, 1, 1, 0, &[0b100, 0])));
+        assert_eq!(InvalidInput, create(&serialized(1, 2, 1, 0, &[0b1100, 0])));
+        assert_eq!(InvalidInput, create(&serialized(1, 2, 1, 1, &[0b0011, 0])));
+        assert_eq!(
+            InvalidInput,
+            create(&serialized(1, 1, 1, 0, &[0b01, 0b110]))
+        );
+        let mut handicap = serialized(1, 1, 1, 0, &[0, 0]);
+        handicap[7] = 2;
+        assert_eq!(InvalidOptions, create(&handicap));
+        // All option flags are in use.
+        let exact = ConnectFour::create(&GameInit::Serialized(&serialized(1, 1, 1, 128, &[0, 0])));
+        assert!(exact.unwrap().options.exact);
+
+        let game =
+            ConnectFour::create(&GameInit::Serialized(&serialized(1, 2, 1, 0, &[0b0110, 0])))
+                .unwrap();
+        assert_eq!(State::Blocked, game[(0, 0)]);
+        assert_eq!(State::X, game[(0, 1)]);
+
+        // The handicap stones are placed again on restart.
+        let mut game = ConnectFour::create(&GameInit::Standard {
+            opts: Some("7x6@4+h2"),
+            legacy: None,
+            state: None,
+        })
+        .unwrap();
+        game.make_move(1, sync(&3)).unwrap();
+        let mut bytes = vec![];
+        game.export_serialized(&mut bytes);
+        let mut copy = ConnectFour::create(&GameInit::Serialized(&bytes)).unwrap();
+        assert_eq!(game.options, copy.options);
+        assert_eq!(game.data.board, copy.data.board);
+        copy.restart();
+        assert_eq!(2, copy.occupied());
+
+        let game = ConnectFour::create(&GameInit::Standard {
+            opts: Some("3x2@2^"),
+            legacy: None,
+            state: Some("X/OX/#o"),
+        })
+        .unwrap();
+        let mut bytes = vec![];
+        game.export_serialized(&mut bytes);
+        let copy = ConnectFour::create(&GameInit::Serialized(&bytes)).unwrap();
+        assert_eq!(game, copy);
+    }
+
+    #[test]
+    fn has_line() {
+        let mut rng = SplitMix64(7);
+        for options in [
+            "7x6@4", "5x9@3", "10x3@5", "4x4@4", "1x1@1", "3x8@1", "2x5@2", "8x1@3", "1x7@3",
+            "12x11@6",
+        ] {
+            let mut game = ConnectFour::create(&GameInit::Standard {
+                opts: Some(options),
+                legacy: None,
+                state: None,
+            })
+            .unwrap();
+
+            for _ in 0..1000 {
+                for x in 0..game.options.width {
+                    for y in 0..game.options.height {
+                        let state = match rng.next() % 5 {
+                            0 | 1 => State::X,
+                            2 | 3 => State::O,
+                            _ => State::Empty,
+                        };
+                        game.set((x, y), state);
+                    }
+                }
+
+                for player in [0, 1] {
+                    // The cell by cell scan serves as oracle.
+                    let expected = game.find_line(State::from_turn(player)).is_some();
+                    assert_eq!(expected, game.has_line(player), "{options}\n{game}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn heights() {
+        let mut game = create_with_state("xo/*/x.o//x#o");
+        assert_eq!(vec![2, 1, 3, 0, 1, 0, 0], game.data.heights);
+        assert_eq!((2, 3), game.free_cell(2));
+        game.make_move(2, sync(&2)).unwrap();
+        game.make_move(1, sync(&1)).unwrap();
+        assert_eq!(vec![2, 2, 4, 0, 1, 0, 0], game.data.heights);
+        game.undo_move().unwrap();
+        assert_eq!(vec![2, 1, 4, 0, 1, 0, 0], game.data.heights);
+
+        game.import_state(Some("xoxoxo/o#x")).unwrap();
+        assert_eq!(vec![6, 1, 0, 0, 0, 0, 0], game.data.heights);
+        assert!(!game.possible_move(0));
+        game.import_state(None).unwrap();
+        assert_eq!(vec![0; 7], game.data.heights);
+
+        let mut game = PopOut::create(&GameInit::Standard {
+            opts: Some("4x3@3>"),
+            legacy: None,
+            state: Some("xo/o#x"),
+        })
+        .unwrap();
+        assert_eq!(vec![2, 1, 0], game.0.data.heights);
+        game.make_move(1, sync(&POP_FLAG)).unwrap();
+        assert_eq!(vec![1, 1, 0], game.0.data.heights);
+        game.0.undo_move().unwrap();
+        assert_eq!(vec![2, 1, 0], game.0.data.heights);
+
+        game.0.rotate_180();
+        assert_eq!(vec![0, 1, 2], game.0.data.heights);
+        game.0.debug_assert_heights();
+    }
+
+    #[test]
+    fn legal_moves() {
+        let mut games = vec![];
+        for state in [
+            None,
+            Some("xoxoxo/x/oxoxox/#o"),
+            Some("xxxx/ooo#X"),
+            Some("*/*#x"),
+        ] {
+            games.push(ConnectFour::create(&GameInit::Standard {
+                opts: None,
+                legacy: None,
+                state,
+            }));
+        }
+        games.push(
+            PopOut::create(&GameInit::Standard {
+                opts: Some("4x3@3"),
+                legacy: None,
+                state: Some("xo/o/xxx/#o"),
+            })
+            .map(|g| g.0),
+        );
+
+        for mut game in games.into_iter().map(Result::unwrap) {
+            let mut expected = vec![];
+            game.get_concrete_moves(player_to_id(game.data.turn), &mut expected)
+                .unwrap();
+            let expected = MoveCode::slice_to_rust(&expected);
+
+            let mut visited = vec![];
+            game.for_each_legal_move(|code| visited.push(code));
+            assert_eq!(expected, visited);
+            assert_eq!(expected.len(), game.legal_move_count());
+        }
+    }
+
+    #[test]
+    fn board_backends() {
+        let mut rng = SplitMix64(3);
+        for options in ["7x6@4", "7x6@4p3", "4x4@3"] {
+            let mut inline = PopOut::create(&GameInit::Standard {
+                opts: Some(options),
+                legacy: None,
+                state: None,
+            })
+            .unwrap()
+            .0;
+            assert!(matches!(inline.data.board, Board::Inline(..)));
+
+            for _ in 0..50 {
+                inline.restart();
+                let mut heap = inline.clone();
+                heap.data.board = Board::Heap(BitVec::new(inline.data.board.len()));
+
+                while inline.data.moves < 100 {
+                    let mut moves = vec![];
+                    inline.for_each_legal_move(|code| moves.push(code));
+                    if moves.is_empty() {
+                        break;
+                    }
+                    let code = moves[rng.next() as usize % moves.len()];
+                    let player = player_to_id(inline.data.turn);
+                    inline.make_move(player, sync(&code)).unwrap();
+                    heap.make_move(player, sync(&code)).unwrap();
+
+                    assert_eq!(inline.data.board.to_bytes(), heap.data.board.to_bytes());
+                    assert_eq!(inline.id(), heap.id());
+                    assert_eq!(inline.data.result, heap.data.result);
+                    assert_eq!(inline.data.winning_line, heap.data.winning_line);
+                }
+
+                while inline.undo_move().is_some() {
+                    heap.undo_move().unwrap();
+                    assert_eq!(inline.data.board.to_bytes(), heap.data.board.to_bytes());
+                }
+                assert!(!heap.data.board.any());
+            }
+        }
+    }
+
+    #[test]
+    fn large_board() {
+        let mut game = ConnectFour::create(&GameInit::Standard {
+            opts: Some("1000x1000@4^"),
+            legacy: None,
+            state: None,
+        })
+        .unwrap();
+        game.make_move(1, sync(&999)).unwrap();
+        assert_eq!(State::X, game[(999, 999)]);
+        assert_eq!((999, 998), game.free_cell(999));
+        game.is_legal_move(2, sync(&999)).unwrap();
+        assert_eq!(
+            InvalidInput,
+            game.is_legal_move(2, sync(&1000)).unwrap_err().code
+        );
+
+        let create = |opts| {
+            ConnectFour::create(&GameInit::Standard {
+                opts: Some(opts),
+                legacy: None,
+                state: None,
+            })
+            .unwrap_err()
+            .code
+        };
+        assert_eq!(InvalidOptions, create("65536x6@4"));
+        assert_eq!(InvalidOptions, create("7x6@100000"));
+    }
+
+    #[test]
+    fn export_options() {
+        for options in [
+            "7x6@4",
+            "4x3@2",
+            "12x1@1>",
+            "5x5@3^!",
+            "6x4@4~",
+            "5x4@3p4<",
+            "7x6@4p3+h2",
+            "8x6@4>!#",
+            "7x6@4~=",
+            "5x4@3p4+h1<z",
+        ] {
+            let mut game = ConnectFour::create(&GameInit::Standard {
+                opts: Some(options),
+                legacy: None,
+                state: None,
+            })
+            .unwrap();
+
+            let mut storage = ValidCString::default();
+            game.export_options(PLAYER_NONE, &mut storage).unwrap();
+            assert_eq!(options, storage.as_ref());
+            assert_eq!(game.options, GameOptions::new(storage.as_ref()).unwrap());
+        }
+    }
+
+    #[test]
+    fn copy_from() {
+        let mut a = create_with_state("O/X#X");
+        let mut b = create_with_state("O/X#o");
+
+        b.copy_from(&mut a).unwrap();
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn export_state() {
+        let mut game = create_with_state("X/OOxO//X#O");
+
+        let expected = "X/OOXO//X///#O";
+        let mut storage = ValidCString::default();
+        game.export_state(PLAYER_NONE, &mut storage).unwrap();
+
+        assert_eq!(expected, storage.as_ref());
+    }
+
+    #[test]
+    fn players_to_move() {
+        let mut game = create_default();
+
+        let mut storage = vec![];
+        game.players_to_move(&mut storage).unwrap();
+        assert_eq!([1], *storage);
+
+        game.import_state(Some("#o")).unwrap();
+        let mut storage = vec![];
+        game.players_to_move(&mut storage).unwrap();
+        assert_eq!([2], *storage);
+
+        game.import_state(Some("/#O")).unwrap();
+        let mut storage = vec![];
+        game.players_to_move(&mut storage).unwrap();
+        assert_eq!([] as [player_id; 0], *storage);
+
+        game.import_state(Some("/x/#-")).unwrap();
+        let mut storage = vec![];
+        game.players_to_move(&mut storage).unwrap();
+        assert_eq!([] as [player_id; 0], *storage);
+    }
+
+    #[test]
+    fn first_player() {
+        let create = |options, state| {
+            ConnectFour::create(&GameInit::Standard {
+                opts: Some(options),
+                legacy: None,
+                state,
+            })
+            .unwrap()
+        };
+
+        let mut game = create("7x6@4o", None);
+        let mut storage = vec![];
+        game.players_to_move(&mut storage).unwrap();
+        assert_eq!([2], *storage);
+        game.make_move(2, sync(&3)).unwrap();
+        let mut storage = vec![];
+        game.players_to_move(&mut storage).unwrap();
+        assert_eq!([1], *storage);
+
+        game.restart();
+        assert_eq!(1, game.data.turn);
+        game.import_state(None).unwrap();
+        assert_eq!(1, game.data.turn);
+        // States name the player to move themselves.
+        game.import_state(Some("#x")).unwrap();
+        assert_eq!(0, game.data.turn);
+        assert_eq!(0, create("7x6@4o", Some("#x")).data.turn);
+
+        let game = create("4x4@3p3z", None);
+        assert_eq!(2, game.data.turn);
+        let mut bytes = vec![];
+        game.export_serialized(&mut bytes);
+        let mut copy = ConnectFour::create(&GameInit::Serialized(&bytes)).unwrap();
+        assert_eq!(game, copy);
+        copy.restart();
+        assert_eq!(2, copy.data.turn);
+        *bytes.last_mut().unwrap() |= 0b11 << 4;
+        let err = ConnectFour::create(&GameInit::Serialized(&bytes)).unwrap_err();
+        assert_eq!(InvalidOptions, err.code);
+
+        // The turn order starts with `O`.
+        let mut game = create("7x6@4o", None);
+        game.import_state_strict(Some("O#x")).unwrap();
+        game.import_state_strict(Some("OX#o")).unwrap();
+        game.import_state_strict(Some("X#o")).unwrap_err();
+    }
+
+    #[test]
+    fn board_is_full() {
+        assert!(!create_default().board_is_full());
+        let mut game = create_with_state("XXXOOO/OOOXXX/XXXOOO/OOOXXX/XXXOOO/OOOXXX/XXXOO#x");
+        assert!(!game.board_is_full());
+        game.make_move(1, sync(&6)).unwrap();
+        assert!(game.board_is_full());
+        assert_eq!(GameResult::Draw, game.data.result);
+        assert!(
+            create_with_state("XXXOOO/OOOXXX/XXXOOO/OOOXXX/XXXOOO/OOOXXX/XXXOOO#X").board_is_full()
+        );
+        assert!(
+            create_with_state(".....*/.....*/.....*/.....*/.....*/.....*/.....*#x").board_is_full()
+        );
+    }
+
+    #[test]
+    fn occupied() {
+        let game = create_default();
+        assert_eq!(0, game.occupied());
+        assert_eq!(42, game.remaining());
+
+        let mut game = create_with_state("XOX/O//XXO#o");
+        assert_eq!(7, game.occupied());
+        assert_eq!(35, game.remaining());
+        game.make_move(2, sync(&4)).unwrap();
+        assert_eq!(8, game.occupied());
+        assert_eq!(34, game.remaining());
+
+        let game = create_with_state("..X*/*/O#x");
+        assert_eq!(2, game.occupied());
+        assert_eq!(2 + 5 + 5 + 6 * 4, game.remaining());
+
+        let game = ConnectFour::create(&GameInit::Standard {
+            opts: Some("4x3@3p3<"),
+            legacy: None,
+            state: Some("xz/oz/z#x"),
+        })
+        .unwrap();
+        assert_eq!(5, game.occupied());
+        assert_eq!(7, game.remaining());
+
+        let full = create_with_state("XXXOOO/OOOXXX/XXXOOO/OOOXXX/XXXOOO/OOOXXX/XXXOOO#X");
+        assert_eq!(42, full.occupied());
+        assert_eq!(0, full.remaining());
+    }
+
+    #[test]
+    fn get_concrete_moves() {
+        let mut game = create_with_state("//XOXOXO//#o");
+
+        let mut storage = vec![];
+        game.get_concrete_moves(1, &mut storage).unwrap();
+        assert_eq!(
+            [] as [move_code; 0],
+            MoveCode::slice_to_rust(&storage).as_ref()
+        );
+
+        let mut storage = vec![];
+        game.get_concrete_moves(2, &mut storage).unwrap();
+        assert_eq!(
+            (0..DEFAULT_WIDTH as move_code)
+                .filter(|&c| c != 2)
+                .collect::<Vec<_>>(),
+            MoveCode::slice_to_rust(&storage)
+        );
+
+        game.import_state(Some("#X")).unwrap();
+        let mut storage = vec![];
+        game.get_concrete_moves(1, &mut storage).unwrap();
+        assert_eq!(
+            [] as [move_code; 0],
+            MoveCode::slice_to_rust(&storage).as_ref()
+        );
+    }
+
+    #[test]
+    fn playable_columns_mask() {
+        for state in ["#x", "XXXOOO//OXOXOX/X#o", "XXXOOO/OOOXXX/////#x", "XXXX#X"] {
+            let mut game = create_with_state(state);
+            let mut moves = vec![];
+            game.get_concrete_moves(player_to_id(game.data.turn), &mut moves)
+                .unwrap();
+            let expected = MoveCode::slice_to_rust(&moves)
+                .into_iter()
+                .fold(0, |mask, column| mask | 1 << column);
+            assert_eq!(expected, game.playable_columns_mask());
+        }
+
+        assert_eq!(
+            0b1111100,
+            create_with_state("XXXOOO/OOOXXX/////#x").playable_columns_mask()
+        );
+        assert_eq!(0, create_with_state("XXXX#X").playable_columns_mask());
+    }
+
+    #[test]
+    fn get_concrete_moves_ordered() {
+        let mut game = create_default();
+        let mut storage = vec![];
+        game.get_concrete_moves_ordered(1, &mut storage).unwrap();
+        assert_eq!([3, 2, 4, 1, 5, 0, 6], *MoveCode::slice_to_rust(&storage));
+
+        let mut storage = vec![];
+        game.get_concrete_moves_ordered(2, &mut storage).unwrap();
+        assert_eq!([] as [move_code; 0], *MoveCode::slice_to_rust(&storage));
+
+        let mut game = ConnectFour::create(&GameInit::Standard {
+            opts: Some("6x2@2"),
+            legacy: None,
+            state: Some("/XO///OX/#o"),
+        })
+        .unwrap();
+        let mut storage = vec![];
+        game.get_concrete_moves_ordered(2, &mut storage).unwrap();
+        assert_eq!([3, 2, 5, 0], *MoveCode::slice_to_rust(&storage));
+
+        let mut game = create_pop_out("X//O/#x");
+        let mut storage = vec![];
+        game.0.get_concrete_moves_ordered(1, &mut storage).unwrap();
+        assert_eq!([2, 1, 3, 0, POP_FLAG], *MoveCode::slice_to_rust(&storage));
+    }
+
+    #[test]
+    fn is_legal_move() {
+        let mut game = create_with_state("/OXOOXO/#o");
+
+        let err = game.is_legal_move(1, sync(&0)).unwrap_err().code;
+        assert_eq!(InvalidInput, err);
+
+        game.is_legal_move(2, sync(&0)).unwrap();
+
+        game.is_legal_move(2, sync(&1)).unwrap_err().code;
+        assert_eq!(InvalidInput, err);
+
+        game.is_legal_move(2, sync(&DEFAULT_WIDTH.into()))
+            .unwrap_err()
+            .code;
+        assert_eq!(InvalidInput, err);
+
+        game.import_state(Some("#X")).unwrap();
+        game.is_legal_move(2, sync(&1)).unwrap_err().code;
+        assert_eq!(InvalidInput, err);
+
+        game.import_state(Some("#-")).unwrap();
+        game.is_legal_move(2, sync(&2)).unwrap_err().code;
+        assert_eq!(InvalidInput, err);
+    }
+
+    #[test]
+    fn is_legal() {
+        let mut rng = SplitMix64(11);
+        for (options, pop_out) in [("7x6@4", false), ("5x4@3p3", true), ("4x3@3<", true)] {
+            for _ in 0..50 {
+                let init = GameInit::Standard {
+                    opts: Some(options),
+                    legacy: None,
+                    state: None,
+                };
+                let mut game = if pop_out {
+                    PopOut::create(&init).unwrap().0
+                } else {
+                    ConnectFour::create(&init).unwrap()
+                };
+
+                for _ in 0..100 {
+                    let mut legal = vec![];
+                    for player in 1..=player_to_id(game.options.players - 1) {
+                        let mut moves = vec![];
+                        game.get_concrete_moves(player, &mut moves).unwrap();
+                        let moves = MoveCode::slice_to_rust(&moves);
+                        for column in 0..=game.lanes() {
+                            for mov in [column.into(), POP_FLAG | move_code::from(column)] {
+                                let expected = moves.contains(&mov);
+                                assert_eq!(expected, game.is_legal(player, mov), "{mov}\n{game}");
+                                let result = game.is_legal_move(player, sync(&mov));
+                                assert_eq!(expected, result.is_ok(), "{mov}\n{game}");
+                            }
+                        }
+                        legal.extend(moves.into_iter().map(|mov| (player, mov)));
+                    }
+
+                    if legal.is_empty() {
+                        break;
+                    }
+                    let (player, mov) = legal[rng.next() as usize % legal.len()];
+                    game.make_move(player, sync(&mov)).unwrap();
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn landing_cell() {
+        let game = create_with_state("XOXOXO/XO/*/.X//#x");
+        assert_eq!(None, game.landing_cell(0));
+        assert_eq!(Some((1, 2)), game.landing_cell(1));
+        assert_eq!(Some((2, 1)), game.landing_cell(2));
+        assert_eq!(Some((3, 2)), game.landing_cell(3));
+        assert_eq!(Some((4, 0)), game.landing_cell(4));
+        assert_eq!(Some((6, 0)), game.landing_cell(6));
+        assert_eq!(None, game.landing_cell(7));
+        assert_eq!(None, game.landing_cell(u16::MAX));
+        assert_eq!(game.free_cell(1), game.landing_cell(1).unwrap());
+
+        let game = ConnectFour::create(&GameInit::Standard {
+            opts: Some("3x2@2>"),
+            legacy: None,
+            state: Some("X/OX#o"),
+        })
+        .unwrap();
+        assert_eq!(Some((1, 0)), game.landing_cell(0));
+        assert_eq!(Some((0, 1)), game.landing_cell(1));
+        assert_eq!(None, game.landing_cell(2));
+
+        let game = create_with_state("XXXX#X");
+        assert_eq!(Some((0, 4)), game.landing_cell(0));
+    }
+
+    #[test]
+    fn restart() {
+        for options in ["7x6@4", "5x4@3p3+h2", "4x6@3<"] {
+            let create = || {
+                PopOut::create(&GameInit::Standard {
+                    opts: Some(options),
+                    legacy: None,
+                    state: None,
+                })
+                .unwrap()
+                .0
+            };
+            let mut game = create();
+            for code in [0, 1, 1, 2, POP_FLAG] {
+                let player = player_to_id(game.data.turn);
+                game.make_move(player, sync(&code)).unwrap();
+            }
+            assert_ne!(create(), game);
+
+            let heights = game.data.heights.as_ptr();
+            game.restart();
+            assert_eq!(create(), game);
+            assert_eq!(heights, game.data.heights.as_ptr());
+        }
+    }
+
+    #[test]
+    fn make_move() {
+        let mut game = create_with_state("/OOO/#x");
+
+        game.make_move(1, sync(&0)).unwrap();
+        game.make_move(2, sync(&1)).unwrap();
+
+        let expected = "X/OOOO/////#O";
+        let mut storage = ValidCString::default();
+        game.export_state(PLAYER_NONE, &mut storage).unwrap();
+        assert_eq!(expected, storage.as_ref());
+
+        game.import_state(Some("XXXOOO/OOOXXX/XXXOOO/OOOXXX/XXXOO/OOOXXX/XXXOOO#o"))
+            .unwrap();
+        game.make_move(2, sync(&4)).unwrap();
+        assert_eq!(GameResult::Draw, game.data.result);
+    }
+
+    #[test]
+    fn make_move_checked() {
+        let mut game = create_with_state("/OOO/#x");
+        assert_eq!(GameResult::Ongoing, game.make_move_checked(1, 0));
+        assert_eq!(1, game.data.turn);
+        assert_eq!(GameResult::Winner, game.make_move_checked(2, 1));
+        assert_eq!(1, game.data.turn);
+
+        game.import_state(Some("XXXOOO/OOOXXX/XXXOOO/OOOXXX/XXXOO/OOOXXX/XXXOOO#o"))
+            .unwrap();
+        assert_eq!(GameResult::Draw, game.make_move_checked(2, 4));
+    }
+
+    #[test]
+    fn is_winning_move() {
+        let game = create_with_state("XXX/OOO/X/X/X#o");
+        // vertical
+        assert!(game.is_winning_move(1, 0));
+        assert!(game.is_winning_move(2, 1));
+        // horizontal
+        assert!(game.is_winning_move(1, 5));
+        assert!(!game.is_winning_move(2, 5));
+        // no line
+        assert!(!game.is_winning_move(1, 6));
+        assert!(!game.is_winning_move(2, 6));
+        assert!(!game.is_winning_move(1, 7));
+        assert_eq!(create_with_state("XXX/OOO/X/X/X#o"), game);
+
+        // diagonal
+        let game = create_with_state("O/XO/XXO/XXX#o");
+        assert!(game.is_winning_move(2, 3));
+        assert!(!game.is_winning_move(2, 4));
+
+        let game = create_with_state("XXXOOO/X/X/X#o");
+        assert!(!game.is_winning_move(1, 0));
+        let game = create_with_state("X/X/X#O");
+        assert!(!game.is_winning_move(1, 3));
+    }
+
+    #[test]
+    fn exact() {
+        let create = |options, state| {
+            ConnectFour::create(&GameInit::Standard {
+                opts: Some(options),
+                legacy: None,
+                state: Some(state),
+            })
+            .unwrap()
+        };
+
+        // Dropping into column 3 makes five in a row.
+        let mut game = create("7x6@4=", "/X/X//X/X/o#x");
+        assert!(!game.is_winning_move(1, 3));
+        assert!(create("7x6@4", "/X/X//X/X/o#x").is_winning_move(1, 3));
+        game.make_move(1, sync(&3)).unwrap();
+        assert_eq!(GameResult::Ongoing, game.data.result);
+        assert_eq!(None, game.scan_for_win());
+
+        // Exactly four still win.
+        let mut game = create("7x6@4=", "/X/X/X/o/O#x");
+        assert!(game.is_winning_move(1, 0));
+        game.make_move(1, sync(&0)).unwrap();
+        assert_eq!(GameResult::Winner, game.data.result);
+        assert_eq!(vec![(0, 0), (1, 0), (2, 0), (3, 0)], game.data.winning_line);
+        assert_eq!(
+            Some((1, vec![(0, 0), (1, 0), (2, 0), (3, 0)])),
+            game.scan_for_win()
+        );
+
+        // A vertical overline crossing a horizontal line of four.
+        let game = create("7x6@4=", "XXXXX/X/X/X#o");
+        assert_eq!(
+            Some((1, vec![(0, 0), (1, 0), (2, 0), (3, 0)])),
+            game.scan_for_win()
+        );
+
+        // Wrapping lines are measured all the way around the board.
+        let game = create("5x1@4~=", "/X/X/X/#x");
+        assert!(game.is_winning_move(1, 0));
+        assert!(game.is_winning_move(1, 4));
+        assert!(!create("5x1@4~=", "X/X//X/X#x").is_winning_move(1, 2));
+        assert!(create("5x1@4~", "X/X//X/X#x").is_winning_move(1, 2));
+        assert!(create("4x1@4~=", "X/X/X/#x").is_winning_move(1, 3));
+    }
+
+    #[test]
+    fn count_winning_replies() {
+        // Dropping next to the two stones of `X` threatens both ends.
+        let mut game = create_with_state("//X/X///OO#x");
+        assert_eq!(2, game.count_winning_replies(1, 4));
+        assert_eq!(2, game.count_winning_replies(1, 1));
+        assert_eq!(1, game.count_winning_replies(1, 0));
+        assert_eq!(1, game.count_winning_replies(1, 5));
+        assert_eq!(0, game.count_winning_replies(1, 6));
+        assert_eq!(0, game.count_winning_replies(2, 4));
+        assert_eq!(0, game.count_winning_replies(1, 7));
+        assert_eq!(create_with_state("//X/X///OO#x"), game);
+
+        let mut game = create_with_state("XOXOXO/X/X#x");
+        assert_eq!(0, game.count_winning_replies(1, 0));
+        assert_eq!(0, create_default().count_winning_replies(1, 3));
+    }
+
+    #[test]
+    fn evaluate() {
+        assert_eq!(0, create_default().evaluate(1));
+        assert_eq!(0, create_with_state("XXX//////OOO#x").evaluate(1));
+        assert_eq!(0, create_with_state("XX/O////X/OO#o").evaluate(2));
+
+        let game = create_with_state("XXX/O/O#o");
+        assert!(game.evaluate(1) > 0);
+        assert_eq!(-game.evaluate(1), game.evaluate(2));
+
+        let game = create_with_state("OOOO/XXX#O");
+        assert!(game.evaluate(2) >= WIN_SCORE);
+        assert!(game.evaluate(1) <= -WIN_SCORE);
+        assert_eq!(0, create_with_state("#-").evaluate(1));
+    }
+
+    #[test]
+    fn undo_move() {
+        let mut game = create_with_state("/OOO/#x");
+        assert_eq!(None, game.undo_move());
+
+        let before = game.clone();
+        game.make_move(1, sync(&0)).unwrap();
+        game.make_move(2, sync(&2)).unwrap();
+        game.make_move(1, sync(&0)).unwrap();
+        game.make_move(2, sync(&1)).unwrap();
+        assert_eq!(GameResult::Winner, game.data.result);
+        assert_eq!(Some(1), game.undo_move());
+        assert_eq!(GameResult::Ongoing, game.data.result);
+        assert_eq!(1, game.data.turn);
+        assert_eq!(State::Empty, game[(1, 3)]);
+        assert_eq!(Some(0), game.undo_move());
+        assert_eq!(Some(2), game.undo_move());
+        assert_eq!(Some(0), game.undo_move());
+        assert_eq!(None, game.undo_move());
+        assert_eq!(before, game);
+
+        game.import_state(None).unwrap();
+        for column in [0, 1, 0, 1, 2] {
+            let player = player_to_id(game.data.turn);
+            game.make_move(player, sync(&column)).unwrap();
+        }
+        while game.undo_move().is_some() {}
+        assert!(!game.data.board.any());
+        assert_eq!(0, game.data.turn);
+        assert_eq!(create_default(), game);
+
+        game.import_state(Some("XXXOOO/OOOXXX/XXXOOO/OOOXXX/XXXOO/OOOXXX/XXXOOO#o"))
+            .unwrap();
+        let before = game.clone();
+        game.make_move(2, sync(&4)).unwrap();
+        assert_eq!(GameResult::Draw, game.data.result);
+        assert_eq!(Some(4), game.undo_move());
+        assert_eq!(before, game);
+    }
+
+    #[test]
+    fn options_from_str() {
+        let options: GameOptions = "5x4@3p3<".parse().unwrap();
+        assert_eq!(GameOptions::new("5x4@3p3<").unwrap(), options);
+
+        let err = "7x6@8".parse::<GameOptions>().unwrap_err();
+        assert_eq!(InvalidOptions, err.code);
+        assert_eq!(GameOptions::new("7x6@8").unwrap_err().message, err.message);
+    }
+
+    #[test]
+    fn max_run() {
+        let message = |options| {
+            GameOptions::new(options)
+                .unwrap_err()
+                .message
+                .expect("no message")
+        };
+        assert_eq!(
+            "length_too_large: length must not exceed 3, the longest line on a 3x3 board",
+            message("3x3@4")
+        );
+        assert_eq!(
+            "length_too_large: length must not exceed 7, the longest line on a 7x2 board",
+            message("7x2@8")
+        );
+        assert_eq!(
+            "length_too_large: length must not exceed 6, the longest line on a 3x2 board \
+             with wrapping lines",
+            message("3x2@7~")
+        );
+        assert_eq!(
+            "length_too_large: length must not exceed 3, the longest line on a 3x3 board \
+             with wrapping lines",
+            message("3x3@4~")
+        );
+
+        // A wrapping diagonal runs through all six cells.
+        let mut game = ConnectFour::create(&GameInit::Standard {
+            opts: Some("3x2@6~"),
+            legacy: None,
+            state: Some("XX/XX/X#x"),
+        })
+        .unwrap();
+        game.make_move(1, sync(&2)).unwrap();
+        assert_eq!(GameResult::Winner, game.data.result);
+    }
+
+    #[test]
+    fn state_from_str() {
+        assert_eq!(State::X, "X".parse().unwrap());
+        assert_eq!(State::X, "x".parse().unwrap());
+        assert_eq!(State::O, "O".parse().unwrap());
+        assert_eq!(State::O, "o".parse().unwrap());
+        assert_eq!(State::Z, "z".parse().unwrap());
+        assert_eq!(State::Y, "Y".parse().unwrap());
+        assert_eq!(State::Blocked, "*".parse().unwrap());
+        assert_eq!(State::Empty, ".".parse().unwrap());
+        assert_eq!(State::Empty, "_".parse().unwrap());
+        assert_eq!(State::Empty, " ".parse().unwrap());
+        // State strings skip spaces instead.
+        let game = create_with_state("X X#o");
+        assert_eq!(State::X, game[(0, 1)]);
+
+        for invalid in ["A", "-", "", "XO"] {
+            assert_eq!(
+                ErrorKind::InvalidPlayer.code(),
+                invalid.parse::<State>().unwrap_err().code
+            );
+        }
+    }
+
+    #[test]
+    fn options_builder() {
+        let build = |w, h, l, f: fn(GameOptionsBuilder) -> GameOptionsBuilder| {
+            f(GameOptions::builder(w, h, l)).build()
+        };
+
+        assert_eq!(
+            GameOptions::new("7x6@4").unwrap(),
+            build(7, 6, 4, |b| b).unwrap()
+        );
+        assert_eq!(GameOptions::default(), build(7, 6, 4, |b| b).unwrap());
+        assert_eq!(
+            GameOptions::new("5x4@3p3+h2<~#").unwrap(),
+            build(5, 4, 3, |b| b
+                .players(3)
+                .handicap(2)
+                .gravity(Direction::W)
+                .wrap(true)
+                .one_based(true))
+            .unwrap()
+        );
+        assert_eq!(
+            GameOptions::new("6x6@5^!").unwrap(),
+            build(6, 6, 5, |b| b.gravity(Direction::N).misere(true)).unwrap()
+        );
+
+        for (options, result) in [
+            ("0x6@4", build(0, 6, 4, |b| b)),
+            ("7x6@8", build(7, 6, 8, |b| b)),
+            ("7x6@4p5", build(7, 6, 4, |b| b.players(5))),
+            ("7x6@4p3!", build(7, 6, 4, |b| b.players(3).misere(true))),
+            ("2x2@2+h5", build(2, 2, 2, |b| b.handicap(5))),
+        ] {
+            let expected = GameOptions::new(options).unwrap_err();
+            let err = result.unwrap_err();
+            assert_eq!(expected.code, err.code, "{options}");
+            assert_eq!(expected.message, err.message, "{options}");
+        }
+
+        let err = build(7, 6, 4, |b| b.gravity(Direction::NE)).unwrap_err();
+        assert_eq!(InvalidOptions, err.code);
+    }
+
+    #[test]
+    fn error_kinds() {
+        fn assert_kind<T>(kind: ErrorKind, result: Result<T>) {
+            let err = result.err().expect("no error");
+            assert_eq!(kind.code(), err.code);
+            let message = err.message.expect("no message");
+            assert!(
+                message.starts_with(&format!("{}: ", kind.tag())),
+                "{message}"
+            );
+        }
+
+        for (kind, options) in [
+            (ErrorKind::UnexpectedSuffix, "7x6@4x"),
+            (ErrorKind::UnexpectedSuffix, "7x6@4x5"),
+            (ErrorKind::Number, "7x6"),
+            (ErrorKind::Number, "7x6@4p+h3"),
+            (ErrorKind::DimensionTooSmall, "0x6@4"),
+            (ErrorKind::DimensionTooLarge, "70000x6@4"),
+            (ErrorKind::LengthTooLarge, "7x6@8"),
+            (ErrorKind::PlayerCount, "7x6@4p5"),
+            (ErrorKind::PlayerCount, "7x6@4p3!"),
+            (ErrorKind::PlayerCount, "7x6@4y"),
+            (ErrorKind::TooManyHandicap, "2x2@2+h5"),
+        ] {
+            assert_kind(kind, GameOptions::new(options));
+        }
+
+        for (kind, state) in [
+            (ErrorKind::TooManyColumns, "X/O/X/O/X/O/X/O#x"),
+            (ErrorKind::TooManyRows, "XXXXXXX#x"),
+            (ErrorKind::InvalidPlayer, "Q#x"),
+            (ErrorKind::InvalidPlayer, "X#q"),
+            (ErrorKind::InvalidPlayer, "Z#x"),
+            (ErrorKind::NoSuchColumn, "X#x@9"),
+            (ErrorKind::ColumnEmpty, "X#x@3"),
+            (ErrorKind::Number, "X#x@a"),
+        ] {
+            assert_kind(kind, create_default().import_state(Some(state)));
+        }
+        for (kind, state) in [
+            (ErrorKind::MissingLine, "xxx/ooo#X"),
+            (ErrorKind::UnexpectedLine, "xxxx/ooo#o"),
+        ] {
+            assert_kind(kind, create_default().import_state_strict(Some(state)));
+        }
+
+        let mut game = create_with_state("XOXOXO#x");
+        assert_kind(ErrorKind::NoSuchColumn, game.is_legal_move(1, sync(&9)));
+        assert_kind(ErrorKind::WrongTurn, game.is_legal_move(2, sync(&1)));
+        assert_kind(ErrorKind::ColumnFull, game.is_legal_move(1, sync(&0)));
+        assert_kind(
+            ErrorKind::PopNotAllowed,
+            game.is_legal_move(1, sync(&POP_FLAG)),
+        );
+        let mut game = create_with_state("XXXX#X");
+        assert_kind(ErrorKind::GameOver, game.is_legal_move(1, sync(&1)));
+        let mut game = create_pop_out("/X#o");
+        assert_kind(
+            ErrorKind::ColumnEmpty,
+            game.is_legal_move(2, sync(&POP_FLAG)),
+        );
+        assert_kind(
+            ErrorKind::ForeignStone,
+            game.is_legal_move(2, sync(&(POP_FLAG | 1))),
+        );
     }
-}
 
-/// Possible states of the game.
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
-enum GameResult {
-    Ongoing,
-    Winner,
-    Draw,
-}
+    #[test]
+    fn clone_config_into() {
+        let mut source = create_with_state("XO*/OX/X#o");
+        source.make_move(2, sync(&3)).unwrap();
+        let mut target = ConnectFour::create(&GameInit::Standard {
+            opts: Some("4x3@3p3"),
+            legacy: None,
+            state: Some("XZ/O#o"),
+        })
+        .unwrap();
+        source.clone_config_into(&mut target);
+        assert_eq!(source, target);
+        assert_eq!(source.id(), target.id());
+        target.make_move(1, sync(&3)).unwrap();
+        source.make_move(1, sync(&3)).unwrap();
+        assert_eq!(source, target);
+
+        // Shrinking reuses the allocations.
+        let small = ConnectFour::create(&GameInit::Standard {
+            opts: Some("4x3@3"),
+            legacy: None,
+            state: Some("X/O#x"),
+        })
+        .unwrap();
+        let heights = target.data.heights.as_ptr();
+        let history = target.data.history.as_ptr();
+        small.clone_config_into(&mut target);
+        assert_eq!(small, target);
+        assert_eq!(heights, target.data.heights.as_ptr());
+        assert_eq!(history, target.data.history.as_ptr());
+    }
 
-impl GameResult {
-    fn is_over(&self) -> bool {
-        self != &Self::Ongoing
+    #[test]
+    fn get_move_hash() {
+        let game = create_default();
+        let codes = [0, 1, 2, 6, POP_FLAG, POP_FLAG | 1];
+        let hashes: Vec<_> = codes.iter().map(|&c| game.get_move_hash(c)).collect();
+        for (i, hash) in hashes.iter().enumerate() {
+            assert!(!hashes[..i].contains(hash));
+        }
+
+        let mut other = create_with_state("X/O/X#o");
+        other.make_move(2, sync(&3)).unwrap();
+        for (&code, &hash) in codes.iter().zip(&hashes) {
+            assert_eq!(hash, game.get_move_hash(code));
+            assert_eq!(hash, other.get_move_hash(code));
+        }
     }
-}
 
-/// Converts a `player_id` to the [`GameData::turn`] boolean.
-///
-/// # Panics
-/// Panics if given numbers other than 1 or 2.
-pub(crate) const fn player_from_id(player: player_id) -> bool {
-    match player {
-        1 => false,
-        2 => true,
-        _ => unreachable!(),
+    #[test]
+    fn undo_move_id() {
+        let positions = [
+            ("7x6@4", "/OOO/XX/X#x"),
+            ("7x6@4", "XXXOOO/OOOXXX/XXXOOO/OOOXXX/XXXOO/OOOXXX/XXXOOO#o"),
+            ("4x4@3!", "XX/OO#x"),
+            ("5x4@3p3", "XZ/O/Z/X/O#z"),
+            ("4x4@3~", "X/O/X/O#x"),
+        ];
+        for (options, state) in positions {
+            for pop_out in [false, true] {
+                let mut game = ConnectFour::create(&GameInit::Standard {
+                    opts: Some(options),
+                    legacy: None,
+                    state: Some(state),
+                })
+                .unwrap();
+                game.options.pop_out = pop_out;
+
+                let mut moves = vec![];
+                game.for_each_legal_move(|code| moves.push(code));
+                assert!(!moves.is_empty());
+                for code in moves {
+                    let id = game.id();
+                    let player = player_to_id(game.data.turn);
+                    game.make_move(player, sync(&code)).unwrap();
+                    assert_ne!(id, game.id());
+                    assert_eq!(Some(code), game.undo_move());
+                    assert_eq!(id, game.id(), "{options} {state} {code}");
+                }
+            }
+        }
     }
-}
 
-/// Converts the [`GameData::turn`] boolean to a `player_id`.
-pub(crate) const fn player_to_id(player: bool) -> player_id {
-    if player {
-        2
-    } else {
-        1
+    #[test]
+    fn perft() {
+        let mut game = create_default();
+        assert_eq!(1, game.perft(0));
+        assert_eq!(7, game.perft(1));
+        assert_eq!(49, game.perft(2));
+        assert_eq!(343, game.perft(3));
+        assert_eq!(2401, game.perft(4));
+        assert_eq!(create_default(), game);
+
+        let mut game = create_with_state("XXX/OOO#x");
+        assert_eq!(7, game.perft(1));
+        assert_eq!(43, game.perft(2));
+        assert_eq!(1, create_with_state("XXXX#X").perft(3));
     }
-}
 
-fn player_string_error(player: impl Display) -> Error {
-    Error::new_dynamic(InvalidInput, format!(r#""{player}" is not a valid player"#))
-}
+    #[test]
+    fn ply_count() {
+        let mut game = create_default();
+        assert_eq!(0, game.ply_count());
+        game.make_move(1, sync(&3)).unwrap();
+        game.make_move(2, sync(&3)).unwrap();
+        game.make_move(1, sync(&0)).unwrap();
+        assert_eq!(3, game.ply_count());
+        game.undo_move().unwrap();
+        assert_eq!(2, game.ply_count());
 
-/// Parse the supplied `string`.
-///
-/// # Errors
-/// Creates a descriptive error message using `name` if `string` is [`None`] or
-/// failed to parse.
-fn parse<D: FromStr>(name: &str, string: Option<&str>) -> Result<D>
-where
-    <D as FromStr>::Err: Display,
-{
-    string
-        .ok_or_else(|| Error::new_dynamic(InvalidInput, format!("missing {name}")))?
-        .parse()
-        .map_err(|e| Error::new_dynamic(InvalidInput, format!("failed to parse {name}: {e}")))
-}
+        game.import_state(Some("XO/O//X#o")).unwrap();
+        assert_eq!(4, game.ply_count());
+        game.import_state(None).unwrap();
+        assert_eq!(0, game.ply_count());
 
-#[cfg(target_pointer_width = "16")]
-const ERROR: () = "16 bit architectures are not supported.";
+        let mut game = create_pop_out("XO/O/X/#x");
+        game.make_move(1, sync(&POP_FLAG)).unwrap();
+        assert_eq!(5, game.0.ply_count());
 
-#[cfg(test)]
-mod tests {
-    use std::ptr::{null, null_mut};
+        let mut bytes = vec![];
+        game.0.export_serialized(&mut bytes);
+        let copy = ConnectFour::create(&GameInit::Serialized(&bytes)).unwrap();
+        assert_eq!(3, copy.ply_count());
+    }
 
-    use mirabel::{
-        error::ErrorCode::{self, InvalidInput, InvalidOptions},
-        game::{GameMethods, PLAYER_NONE},
-        MoveDataSync,
-    };
+    #[test]
+    fn winning_line() {
+        let mut game = create_with_state("X/X//X/O/O/O#o");
+        assert_eq!(None, game.winning_line());
+
+        game.make_move(2, sync(&6)).unwrap();
+        assert_eq!(None, game.winning_line());
+        game.make_move(1, sync(&2)).unwrap();
+        assert_eq!(
+            Some([(0, 0), (1, 0), (2, 0), (3, 0)].as_slice()),
+            game.winning_line()
+        );
+
+        game.undo_move().unwrap();
+        assert_eq!(None, game.winning_line());
+        game.make_move(1, sync(&2)).unwrap();
+        assert!(game.winning_line().is_some());
+        game.import_state(Some("XXXX#X")).unwrap();
+        assert_eq!(None, game.winning_line());
+
+        let mut game = create_with_state("OOOX/OOX/OX/#x");
+        game.make_move(1, sync(&3)).unwrap();
+        assert_eq!(
+            Some([(0, 3), (1, 2), (2, 1), (3, 0)].as_slice()),
+            game.winning_line()
+        );
+    }
+
+    #[test]
+    fn pop_out() {
+        let mut game = create_pop_out("XOOX/O//#x");
+        let mut storage = vec![];
+        game.get_concrete_moves(1, &mut storage).unwrap();
+        assert_eq!([1, 2, 3, POP_FLAG], *MoveCode::slice_to_rust(&storage));
+
+        let err = game.is_legal_move(1, sync(&(POP_FLAG | 1))).unwrap_err();
+        assert_eq!(InvalidInput, err.code);
+        let err = game.is_legal_move(1, sync(&(POP_FLAG | 2))).unwrap_err();
+        assert_eq!(InvalidInput, err.code);
+        let err = game.is_legal_move(1, sync(&(POP_FLAG | 4))).unwrap_err();
+        assert_eq!(InvalidInput, err.code);
+        game.is_legal_move(1, sync(&POP_FLAG)).unwrap();
+
+        game.make_move(1, sync(&POP_FLAG)).unwrap();
+        assert_eq!(GameResult::Ongoing, game.0.data.result);
+        assert_eq!(1, game.0.data.turn);
+        assert_eq!(State::O, game.0[(0, 0)]);
+        assert_eq!(State::O, game.0[(0, 1)]);
+        assert_eq!(State::X, game.0[(0, 2)]);
+        assert_eq!(State::Empty, game.0[(0, 3)]);
+
+        assert_eq!(Some(POP_FLAG), game.0.undo_move());
+        assert_eq!(create_pop_out("XOOX/O//#x"), game);
+
+        // A pop creating lines for both players wins for the popping player.
+        let mut game = create_pop_out("XOX/OX/OX/#x");
+        game.make_move(1, sync(&POP_FLAG)).unwrap();
+        assert_eq!(GameResult::Winner, game.0.data.result);
+        assert_eq!(0, game.0.data.turn);
+        assert_eq!(
+            Some([(0, 1), (1, 1), (2, 1)].as_slice()),
+            game.0.winning_line()
+        );
+
+        // A pop creating a line only for the opponent loses.
+        let mut game = create_pop_out("XO/O/O/#x");
+        game.make_move(1, sync(&POP_FLAG)).unwrap();
+        assert_eq!(GameResult::Winner, game.0.data.result);
+        let mut storage = vec![];
+        game.get_results(&mut storage).unwrap();
+        assert_eq!([2], *storage);
+        assert_eq!(Some(POP_FLAG), game.0.undo_move());
+        assert_eq!(create_pop_out("XO/O/O/#x"), game);
+
+        assert_eq!(POP_FLAG | 3, game.get_move_data(1, " p3 ").unwrap());
+        let mut storage = ValidCString::default();
+        game.get_move_str(PLAYER_NONE, sync(&(POP_FLAG | 3)), &mut storage)
+            .unwrap();
+        assert_eq!("p3", storage.as_ref());
+
+        // The last move may have emptied its column by popping.
+        let game = create_pop_out("/X//#o@0");
+        assert!(game.0.options.pop_out);
+        assert_eq!(Some(0), game.0.last_column());
+        let err = ConnectFour::create(&GameInit::Standard {
+            opts: Some("4x4@3"),
+            legacy: None,
+            state: Some("/X//#o@0"),
+        })
+        .unwrap_err();
+        assert_eq!(InvalidInput, err.code);
+
+        let mut game = create_with_state("X/#o");
+        let err = game.get_move_data(1, "p0").unwrap_err();
+        assert_eq!(InvalidInput, err.code);
+        let err = game.is_legal_move(2, sync(&POP_FLAG)).unwrap_err();
+        assert_eq!(InvalidInput, err.code);
+    }
+
+    #[test]
+    fn misere() {
+        let mut game = ConnectFour::create(&GameInit::Standard {
+            opts: Some("7x6@4!"),
+            legacy: None,
+            state: Some("XXX/OOO#x"),
+        })
+        .unwrap();
+        assert!(!game.is_winning_move(1, 0));
+
+        game.make_move(1, sync(&0)).unwrap();
+        assert!(game.data.result.is_over());
+        let mut storage = vec![];
+        game.get_results(&mut storage).unwrap();
+        assert_eq!([2], *storage);
+        assert!(game.winning_line().is_some());
+
+        game.undo_move().unwrap();
+        game.make_move(1, sync(&2)).unwrap();
+        let mut storage = vec![];
+        game.get_results(&mut storage).unwrap();
+        assert_eq!([] as [player_id; 0], *storage);
+
+        let mut bytes = vec![];
+        game.export_serialized(&mut bytes);
+        let copy = ConnectFour::create(&GameInit::Serialized(&bytes)).unwrap();
+        assert!(copy.options.misere);
+    }
+
+    #[test]
+    fn blocked() {
+        let mut game = create_with_state("X/..*/*#o");
+        assert_eq!(State::Blocked, game[(1, 2)]);
+        assert_eq!(1, game.ply_count());
+        assert_eq!((1, 3), game.free_cell(1));
+        game.make_move(2, sync(&1)).unwrap();
+        assert_eq!(State::O, game[(1, 3)]);
+        assert_eq!(State::Empty, game[(1, 0)]);
+
+        let mut storage = ValidCString::default();
+        game.export_state(PLAYER_NONE, &mut storage).unwrap();
+        assert_eq!("X/..*O/*////#x", storage.as_ref());
+
+        game.undo_move().unwrap();
+        assert_eq!(create_with_state("X/..*/*#o"), game);
+
+        // Blocked cells neither count as stones nor as empty fields.
+        let game = create_with_state("XXX*#x");
+        assert!(!game.is_winning_move(1, 0));
+        let mut moves = vec![];
+        create_with_state("******/X#o")
+            .get_concrete_moves(2, &mut moves)
+            .unwrap();
+        assert_eq!([1, 2, 3, 4, 5, 6], *MoveCode::slice_to_rust(&moves));
+    }
+
+    #[test]
+    fn wrap() {
+        let create = |opts, state| {
+            ConnectFour::create(&GameInit::Standard {
+                opts: Some(opts),
+                legacy: None,
+                state: Some(state),
+            })
+            .unwrap()
+        };
+
+        let mut game = create("7x6@4~", "XO/////X/XO#x");
+        assert!(game.is_winning_move(1, 1));
+        game.make_move(1, sync(&1)).unwrap();
+        assert_eq!(GameResult::Winner, game.data.result);
+        assert_eq!(
+            Some([(5, 0), (6, 0), (0, 0), (1, 0)].as_slice()),
+            game.winning_line()
+        );
+
+        let mut game = create("7x6@4", "XO/////X/XO#x");
+        game.make_move(1, sync(&1)).unwrap();
+        assert_eq!(GameResult::Ongoing, game.data.result);
+
+        // A row of three must not be counted twice.
+        let mut game = create("3x4@4~", "X/X/#x");
+        assert!(!game.is_winning_move(1, 2));
+        game.make_move(1, sync(&2)).unwrap();
+        assert_eq!(GameResult::Ongoing, game.data.result);
+    }
+
+    #[test]
+    fn get_results() {
+        let mut game = create_with_state("/OXO/#x");
+
+        let mut storage = vec![];
+        game.get_results(&mut storage).unwrap();
+        assert_eq!([] as [player_id; 0], *storage);
+        assert!(!game.is_terminal());
+
+        game.import_state(Some("OOOO#O")).unwrap();
+        let mut storage = vec![];
+        game.get_results(&mut storage).unwrap();
+        assert_eq!([2], *storage);
+        assert!(game.is_terminal());
+
+        game.import_state(Some("#-")).unwrap();
+        let mut storage = vec![];
+        game.get_results(&mut storage).unwrap();
+        assert_eq!([] as [player_id; 0], *storage);
+        assert!(game.is_terminal());
+        let mut storage = vec![];
+        game.players_to_move(&mut storage).unwrap();
+        assert_eq!([] as [player_id; 0], *storage);
+
+        let mut game = ConnectFour::create(&GameInit::Standard {
+            opts: Some("2x1@2"),
+            legacy: None,
+            state: None,
+        })
+        .unwrap();
+        game.make_move(1, sync(&0)).unwrap();
+        assert!(!game.is_terminal());
+        game.make_move(2, sync(&1)).unwrap();
+        assert!(game.is_terminal());
+        assert_eq!(GameResult::Draw, game.data.result);
+
+        // Winning on the last free cell.
+        let mut game = ConnectFour::create(&GameInit::Standard {
+            opts: Some("3x1@2"),
+            legacy: None,
+            state: Some("X//O#x"),
+        })
+        .unwrap();
+        game.make_move(1, sync(&1)).unwrap();
+        assert!(game.is_terminal());
+        assert_eq!(GameResult::Winner, game.data.result);
+        assert_eq!(Some([(0, 0), (1, 0)].as_slice()), game.winning_line());
+        let mut storage = vec![];
+        game.get_results(&mut storage).unwrap();
+        assert_eq!([1], *storage);
+    }
+
+    #[test]
+    fn run_length_format() {
+        let mut game = ConnectFour::create(&GameInit::Standard {
+            opts: Some("100x100@4"),
+            legacy: None,
+            state: None,
+        })
+        .unwrap();
+        for code in [0, 0, 0, 0, 0, 99, 50, 50] {
+            let player = player_to_id(game.data.turn);
+            game.make_move(player, sync(&code)).unwrap();
+        }
+
+        let mut plain = ValidCString::default();
+        game.export_state_as(PLAYER_NONE, StateFormat::Plain, &mut plain)
+            .unwrap();
+        let mut rle = ValidCString::default();
+        game.export_state_as(PLAYER_NONE, StateFormat::RunLength, &mut rle)
+            .unwrap();
+        assert_eq!("XOXOX/50XO/49O#x", rle.as_ref());
+        assert!(rle.as_ref().len() * 5 < plain.as_ref().len());
+
+        let mut imported = game.clone();
+        imported
+            .import_state_as(StateFormat::RunLength, Some(rle.as_ref()))
+            .unwrap();
+        let mut storage = ValidCString::default();
+        imported.export_state(PLAYER_NONE, &mut storage).unwrap();
+        assert_eq!(plain.as_ref(), storage.as_ref());
+
+        let mut game = create_default();
+        game.import_state_as(StateFormat::RunLength, Some("3:X3/*O2 /4.Z#o@1"))
+            .unwrap_err();
+        game.import_state_as(StateFormat::RunLength, Some("3:X3/*O2 /4.X#o@1"))
+            .unwrap();
+        let mut storage = ValidCString::default();
+        game.export_state(PLAYER_NONE, &mut storage).unwrap();
+        assert_eq!("XXX/*OO////.X/#o", storage.as_ref());
+        assert_eq!(3, game.ply_count());
+        assert_eq!(Some(1), game.last_column());
+        game.import_state_as(StateFormat::RunLength, None).unwrap();
+        assert_eq!(create_default(), game);
+
+        for state in ["X6#x", "/6#x", "X2.4#x"] {
+            game.import_state_as(StateFormat::RunLength, Some(state))
+                .unwrap();
+        }
+        for state in ["X7#x", "/7#x", "X2.5#x", "3X#x", "X0#x", "X99999#x"] {
+            assert!(
+                game.import_state_as(StateFormat::RunLength, Some(state))
+                    .is_err(),
+                "{state}"
+            );
+        }
+    }
+
+    #[test]
+    fn compact_format() {
+        for state in ["X#o", "#x", "/O/X*#x", "X/O/X/O/X/O/X#o", "//.X//O#O"] {
+            let mut game = create_with_state(state);
+            let mut compact = ValidCString::default();
+            game.export_state_as(PLAYER_NONE, StateFormat::Compact, &mut compact)
+                .unwrap();
+            assert_eq!(state, compact.as_ref());
+
+            let mut imported = create_default();
+            imported
+                .import_state_as(StateFormat::Compact, Some(compact.as_ref()))
+                .unwrap();
+            assert_eq!(game.data.board, imported.data.board);
+            let (mut plain, mut storage) = (ValidCString::default(), ValidCString::default());
+            game.export_state(PLAYER_NONE, &mut plain).unwrap();
+            imported.export_state(PLAYER_NONE, &mut storage).unwrap();
+            assert_eq!(plain.as_ref(), storage.as_ref());
+        }
+
+        let mut game = create_with_state("X#o");
+        let mut plain = ValidCString::default();
+        game.export_state(PLAYER_NONE, &mut plain).unwrap();
+        assert_eq!("X//////#o", plain.as_ref());
+    }
+
+    #[test]
+    fn move_number_prefix() {
+        let mut game = create_default();
+        game.import_state(Some("XO/O//#o")).unwrap();
+        assert_eq!(3, game.ply_count());
+        let expected = game.clone();
+
+        game.import_state(Some(" 12 : XO/O//#o")).unwrap();
+        assert_eq!(12, game.ply_count());
+        game.data.moves = 3;
+        assert_eq!(expected, game);
+
+        game.import_state(Some("12:XO/O//#o@1")).unwrap();
+        assert_eq!(12, game.ply_count());
+        game.make_move(2, sync(&3)).unwrap();
+        assert_eq!(13, game.ply_count());
+        let mut storage = ValidCString::default();
+        game.export_state(PLAYER_NONE, &mut storage).unwrap();
+        assert_eq!("XO/O//O///#x", storage.as_ref());
+
+        for state in [":XO/O//#o", "x:XO/O//#o", "-1:XO/O//#o", "1:2:XO#o"] {
+            assert!(game.import_state(Some(state)).is_err(), "{state}");
+        }
+    }
+
+    #[test]
+    fn scores() {
+        let mut game = create_with_state("/OXO/#x");
+        assert_eq!(None, game.scores());
+
+        game.import_state(Some("XXXX#X")).unwrap();
+        assert_eq!(Some(vec![1, -1]), game.scores());
+        game.import_state(Some("OOOO#O")).unwrap();
+        assert_eq!(Some(vec![-1, 1]), game.scores());
+        game.import_state(Some("#-")).unwrap();
+        assert_eq!(Some(vec![0, 0]), game.scores());
+
+        let mut game = ConnectFour::create(&GameInit::Standard {
+            opts: Some("7x6@4p3"),
+            legacy: None,
+            state: Some("ZZZZ#Z"),
+        })
+        .unwrap();
+        assert_eq!(Some(vec![-1, -1, 1]), game.scores());
+        game.import_state(Some("#z")).unwrap();
+        assert_eq!(None, game.scores());
+    }
+
+    #[test]
+    fn id() {
+        let mut a = create_default();
+        let mut b = create_default();
+        assert_eq!(a.id(), b.id());
+
+        a.make_move(1, sync(&0)).unwrap();
+        a.make_move(2, sync(&1)).unwrap();
+        a.make_move(1, sync(&2)).unwrap();
+        b.make_move(1, sync(&2)).unwrap();
+        b.make_move(2, sync(&1)).unwrap();
+        assert_ne!(a.id(), b.id());
+        b.make_move(1, sync(&0)).unwrap();
+        assert_eq!(a.id(), b.id());
+
+        let c = create_with_state("X/O/X#o");
+        assert_eq!(a.id(), c.id());
+        let d = create_with_state("X/O/X#x");
+        assert_ne!(a.id(), d.id());
+        let e = create_with_state("X/O/X#X");
+        assert_ne!(d.id(), e.id());
+
+        b.copy_from(&mut create_default()).unwrap();
+        assert_eq!(create_default().id(), b.id());
+        a.import_state(None).unwrap();
+        assert_eq!(b.id(), a.id());
+
+        // Ids are stable across runs.
+        assert_eq!(0x923c_53e7_2874_eed4, create_with_state("X#o").id());
+
+        // The numbers for the players to move do not cancel out each other.
+        let turns = a.zobrist.turns;
+        for (i, a) in turns.iter().enumerate() {
+            for b in &turns[i + 1..] {
+                assert_ne!(a, b);
+                assert!(!turns.contains(&(a ^ b)));
+            }
+        }
+    }
+
+    #[test]
+    fn get_move_code() {
+        let mut game = create_default();
+
+        let mov = game.get_move_data(PLAYER_NONE, " 4 ").unwrap();
+        assert_eq!(4, mov);
+
+        let err = game.get_move_data(PLAYER_NONE, "-3").unwrap_err().code;
+        assert_eq!(InvalidInput, err);
+    }
+
+    #[test]
+    fn letter_notation() {
+        let mut game = create_default();
+
+        assert_eq!(2, game.get_move_data(PLAYER_NONE, "c").unwrap());
+        assert_eq!(2, game.get_move_data(PLAYER_NONE, " C ").unwrap());
+        assert_eq!(15, game.get_move_data(PLAYER_NONE, "P").unwrap());
+        for string in ["{", "cd", "pc", "pp"] {
+            let err = game.get_move_data(PLAYER_NONE, string).unwrap_err().code;
+            assert_eq!(InvalidInput, err, "{string}");
+        }
+
+        // The pop prefix is no column letter in Pop Out.
+        let mut game = PopOut::create(&GameInit::Default).unwrap();
+        assert_eq!(2 | POP_FLAG, game.get_move_data(PLAYER_NONE, "pC").unwrap());
+        assert_eq!(
+            15 | POP_FLAG,
+            game.get_move_data(PLAYER_NONE, "p15").unwrap()
+        );
+        assert_eq!(15, game.get_move_data(PLAYER_NONE, "15").unwrap());
+        for string in ["p", "P", "pp", "pP", " p "] {
+            let err = game.get_move_data(PLAYER_NONE, string).unwrap_err().code;
+            assert_eq!(InvalidInput, err, "{string}");
+        }
+    }
+
+    #[test]
+    fn move_list() {
+        let mut game = create_default();
+        game.import_move_list(" 3 2\t4 3 ").unwrap();
+        let mut storage = ValidCString::default();
+        game.export_move_list(&mut storage);
+        assert_eq!("3 2 4 3", storage.as_ref());
 
-    use super::*;
+        let mut other = create_default();
+        other.import_move_list(storage.as_ref()).unwrap();
+        assert_eq!(game, other);
 
-    #[test]
-    fn get_game_methods() {
-        unsafe {
-            let mut count = 0;
-            plugin_get_game_methods(&mut count, null_mut());
-            assert_eq!(1, count);
+        let mut game = PopOut::create(&GameInit::Default).unwrap();
+        game.0.import_move_list("1 2 p1").unwrap();
+        let mut storage = ValidCString::default();
+        game.0.export_move_list(&mut storage);
+        assert_eq!("1 2 p1", storage.as_ref());
 
-            let mut count = 0;
-            let mut methods = null();
-            plugin_get_game_methods(&mut count, &mut methods);
-            assert_eq!(1, count);
-            assert_ne!(null(), methods);
-        }
+        let mut storage = ValidCString::default();
+        create_default().export_move_list(&mut storage);
+        assert_eq!("", storage.as_ref());
     }
 
     #[test]
-    fn create() {
-        let game = ConnectFour::create(&GameInit::Default).unwrap();
-        assert_eq!(DEFAULT_WIDTH, game.options.width);
-        assert_eq!(DEFAULT_HEIGHT, game.options.height);
-        assert_eq!(DEFAULT_LENGTH, game.options.length);
+    fn move_list_illegal() {
+        let mut game = create_with_state("xoxoxo//#x");
+        let before = game.clone();
+
+        let err = game.import_move_list("1 0").unwrap_err();
+        assert_eq!(InvalidInput, err.code);
+        assert_eq!(before, game);
+        let err = game.import_move_list("1 9").unwrap_err().code;
+        assert_eq!(InvalidInput, err);
+        let err = game.import_move_list("1 x1").unwrap_err().code;
+        assert_eq!(InvalidInput, err);
+        assert_eq!(before, game);
+    }
 
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde() {
         let game = ConnectFour::create(&GameInit::Standard {
-            opts: Some("4x3@2"),
+            opts: Some("5x4@3!"),
             legacy: None,
-            state: None,
+            state: Some("*xo/o/x#o"),
         })
         .unwrap();
 
-        assert_eq!(4, game.options.width);
-        assert_eq!(3, game.options.height);
-        assert_eq!(2, game.options.length);
-
-        fn create(string: &str) -> ErrorCode {
-            ConnectFour::create(&GameInit::Standard {
-                opts: Some(string),
-                legacy: None,
-                state: None,
-            })
-            .unwrap_err()
-            .code
-        }
-        assert_eq!(InvalidInput, create(""));
-        assert_eq!(InvalidInput, create("7x4"));
-        assert_eq!(InvalidInput, create("-5x4@2"));
-        assert_eq!(InvalidOptions, create("4x4@5"));
-        assert_eq!(InvalidOptions, create("2x0@1"));
+        let json = serde_json::to_string(&game).unwrap();
+        let imported: ConnectFour = serde_json::from_str(&json).unwrap();
+        assert_eq!(game.id(), imported.id());
+        assert_eq!(game.options, imported.options);
+        assert_eq!(json, serde_json::to_string(&imported).unwrap());
+
+        let json = serde_json::to_string(&GameResult::Draw).unwrap();
+        assert_eq!(GameResult::Draw, serde_json::from_str(&json).unwrap());
+        let json = serde_json::to_string(&State::Blocked).unwrap();
+        assert_eq!(State::Blocked, serde_json::from_str(&json).unwrap());
+
+        let mut value = serde_json::to_value(&game).unwrap();
+        value["board"].as_array_mut().unwrap().pop();
+        assert!(serde_json::from_value::<ConnectFour>(value).is_err());
+        let mut value = serde_json::to_value(&game).unwrap();
+        value["options"]["width"] = 0.into();
+        assert!(serde_json::from_value::<ConnectFour>(value).is_err());
     }
 
     #[test]
-    fn import_state() {
-        let mut game = create_with_state("XO/O/////X#O");
-        assert!(game.data.turn);
-        assert_eq!(GameResult::Winner, game.data.result);
-        assert_eq!(State::X, game[(0, 0)]);
-        assert_eq!(State::O, game[(0, 1)]);
-        assert_eq!(State::O, game[(1, 0)]);
-        assert_eq!(State::Empty, game[(1, 1)]);
-        assert_eq!(State::X, game[(6, 0)]);
+    fn import_state_strict() {
+        let mut game = create_default();
+        game.import_state_strict(Some("xo/x#o")).unwrap();
+        assert_eq!(1, game.data.turn);
+        game.import_state_strict(Some("xo/xo#x")).unwrap();
+        game.import_state_strict(Some("xo/o/x#-")).unwrap();
+        game.import_state_strict(None).unwrap();
+
+        // Double turns
+        for string in ["xx/o#x", "xo/x#x", "xx/#x", "o#x", "ooo/x#o"] {
+            let err = game.import_state_strict(Some(string)).unwrap_err().code;
+            assert_eq!(InvalidInput, err, "{string}");
+        }
+        game.import_state(Some("xx/#x")).unwrap();
 
-        game.import_state(None).unwrap();
-        assert_eq!(false, game.data.turn);
-        assert_eq!(GameResult::Ongoing, game.data.result);
-        assert!(!game.data.board.any());
-        assert_eq!(
-            2 * usize::from(game.options.width) * usize::from(game.options.height),
-            game.data.board.len()
-        );
+        // X won with the last move.
+        game.import_state_strict(Some("xxxx/ooo#X")).unwrap();
+        let err = game
+            .import_state_strict(Some("xxxx/ooo#O"))
+            .unwrap_err()
+            .code;
+        assert_eq!(InvalidInput, err);
+        // O won with the last move.
+        game.import_state_strict(Some("xxx/oooo/x#O")).unwrap();
 
-        game.import_state(Some("/XO//#-")).unwrap();
-        assert_eq!(GameResult::Draw, game.data.result);
-        assert_eq!(State::X, game[(1, 0)]);
-        assert_eq!(State::O, game[(1, 1)]);
-        assert_eq!(State::Empty, game[(1, 2)]);
+        let mut game = ConnectFour::create(&GameInit::Standard {
+            opts: Some("7x6@4!"),
+            legacy: None,
+            state: None,
+        })
+        .unwrap();
+        game.import_state_strict(Some("xxxx/ooo#O")).unwrap();
+        let err = game
+            .import_state_strict(Some("xxxx/ooo#X"))
+            .unwrap_err()
+            .code;
+        assert_eq!(InvalidInput, err);
 
-        fn assert_invalid(game: &mut ConnectFour, string: &str) {
-            let err = game.import_state(Some(string)).unwrap_err().code;
-            assert_eq!(InvalidInput, err);
+        let mut game = PopOut::create(&GameInit::Default).unwrap();
+        game.0.import_state_strict(Some("xx/#o")).unwrap();
+
+        // Lines need to fit the result.
+        let mut game = create_default();
+        for string in ["xxxx/ooo#o", "xxx/ooo#X", "xxxxo/ooo/o/o/x/x/x#-"] {
+            let err = game.import_state_strict(Some(string)).unwrap_err().code;
+            assert_eq!(InvalidInput, err, "{string}");
         }
-        assert_invalid(&mut game, "///////#x");
-        assert_invalid(&mut game, "XXXXXXXXXX#-");
-        assert_invalid(&mut game, "X/O/X#F");
     }
 
     #[test]
-    fn copy_from() {
-        let mut a = create_with_state("O/X#X");
-        let mut b = create_with_state("O/X#o");
-
-        b.copy_from(&mut a).unwrap();
-        assert_eq!(a, b);
+    fn scan_for_win() {
+        let positions = [
+            ("4x4@3", "#x", None),
+            ("4x4@3", "xo/ox/xo/ox#x", None),
+            ("4x4@3", "/ooo/#x", Some((2, vec![(1, 0), (1, 1), (1, 2)]))),
+            ("4x4@3", "/o/o/o#x", Some((2, vec![(1, 0), (2, 0), (3, 0)]))),
+            (
+                "4x4@3",
+                "ooox/oox/ox#x",
+                Some((1, vec![(0, 3), (1, 2), (2, 1)])),
+            ),
+            (
+                "4x4@3",
+                "/x/ox/oox#o",
+                Some((1, vec![(1, 0), (2, 1), (3, 2)])),
+            ),
+            (
+                "4x4@3",
+                "xxx/ooo#o",
+                Some((1, vec![(0, 0), (0, 1), (0, 2)])),
+            ),
+            (
+                "4x4@3p3",
+                "xo/zzz#x",
+                Some((3, vec![(1, 0), (1, 1), (1, 2)])),
+            ),
+            (
+                "4x4@3~",
+                "x//x/x#o",
+                Some((1, vec![(2, 0), (3, 0), (0, 0)])),
+            ),
+        ];
+        for (options, state, expected) in positions {
+            let game = ConnectFour::create(&GameInit::Standard {
+                opts: Some(options),
+                legacy: None,
+                state: Some(state),
+            })
+            .unwrap();
+            assert_eq!(expected, game.scan_for_win(), "{options} {state}");
+        }
+        assert_eq!(8, Direction::all().len());
+        for direction in Direction::all() {
+            assert!(Direction::all().contains(&direction.inv()));
+        }
     }
 
     #[test]
-    fn export_state() {
-        let mut game = create_with_state("X/OOxO//X#O");
-
-        let expected = "X/OOXO//X///#O";
-        let mut storage = ValidCString::default();
-        game.export_state(PLAYER_NONE, &mut storage).unwrap();
-
-        assert_eq!(expected, storage.as_ref());
+    fn mirror_horizontal() {
+        let mut game = create_with_state("xo/o/*x///x#o");
+        let original = game.clone();
+
+        game.mirror_horizontal();
+        assert_eq!(create_with_state("/x///*x/o/xo#o").data, game.data);
+        game.mirror_horizontal();
+        assert_eq!(original, game);
+
+        let mut game = create_with_state("x/x/x//ooo#x");
+        game.make_move(1, sync(&3)).unwrap();
+        game.mirror_horizontal();
+        assert_eq!(
+            Some([(6, 0), (5, 0), (4, 0), (3, 0)].as_slice()),
+            game.winning_line()
+        );
+        game.undo_move().unwrap();
+        assert_eq!(create_with_state("//ooo//x/x/x#x").id(), game.id());
     }
 
     #[test]
-    fn players_to_move() {
-        let mut game = create_default();
-
-        let mut storage = vec![];
-        game.players_to_move(&mut storage).unwrap();
-        assert_eq!([1], *storage);
-
-        game.import_state(Some("#o")).unwrap();
-        let mut storage = vec![];
-        game.players_to_move(&mut storage).unwrap();
-        assert_eq!([2], *storage);
+    fn rotate_180() {
+        let mut game = create_with_state("xo/o/*x///x#o");
+        let original = game.clone();
+
+        game.rotate_180();
+        assert_eq!(Direction::N, game.options.gravity);
+        assert_eq!(State::X, game[(6, 5)]);
+        assert_eq!(State::O, game[(5, 5)]);
+        assert_eq!(State::Blocked, game[(4, 5)]);
+        assert_eq!(State::X, game[(4, 4)]);
+        assert_eq!(State::X, game[(1, 5)]);
+        game.make_move(2, sync(&1)).unwrap();
+        assert_eq!(State::O, game[(1, 4)]);
+        game.undo_move().unwrap();
 
-        game.import_state(Some("/#O")).unwrap();
-        let mut storage = vec![];
-        game.players_to_move(&mut storage).unwrap();
-        assert_eq!([] as [player_id; 0], *storage);
+        game.rotate_180();
+        assert_eq!(original, game);
+    }
 
-        game.import_state(Some("/x/#-")).unwrap();
-        let mut storage = vec![];
-        game.players_to_move(&mut storage).unwrap();
-        assert_eq!([] as [player_id; 0], *storage);
+    #[test]
+    fn canonical_id() {
+        let game = create_with_state("xo/o/*x///x#o");
+        let mut mirror = game.clone();
+        mirror.mirror_horizontal();
+        assert_ne!(game.id(), mirror.id());
+        assert_eq!(game.canonical_id(), mirror.canonical_id());
+        assert!(game.canonical_id() == game.id() || game.canonical_id() == mirror.id());
+
+        let symmetric = create_with_state("x//o/xo/o//x#x");
+        assert_eq!(symmetric.id(), symmetric.canonical_id());
+
+        let other = create_with_state("xo/o/*x///x#x");
+        assert_ne!(game.canonical_id(), other.canonical_id());
     }
 
+    #[cfg(feature = "rand")]
     #[test]
-    fn get_concrete_moves() {
-        let mut game = create_with_state("//XOXOXO//#o");
+    fn random_move() {
+        use rand::{rngs::StdRng, SeedableRng};
 
-        let mut storage = vec![];
-        game.get_concrete_moves(1, &mut storage).unwrap();
-        assert_eq!(
-            [] as [move_code; 0],
-            MoveCode::slice_to_rust(&storage).as_ref()
-        );
+        let mut rng = StdRng::seed_from_u64(42);
+        for options in ["7x6@4", "100x2@2"] {
+            let mut game = ConnectFour::create(&GameInit::Standard {
+                opts: Some(options),
+                legacy: None,
+                state: None,
+            })
+            .unwrap();
 
-        let mut storage = vec![];
-        game.get_concrete_moves(2, &mut storage).unwrap();
-        assert_eq!(
-            (0..DEFAULT_WIDTH as move_code)
-                .filter(|&c| c != 2)
-                .collect::<Vec<_>>(),
-            MoveCode::slice_to_rust(&storage)
-        );
+            let mut seen = vec![false; game.options.width.into()];
+            for _ in 0..1000 {
+                seen[usize::try_from(game.random_move(&mut rng).unwrap()).unwrap()] = true;
+            }
+            assert!(seen.iter().all(|&s| s));
+
+            for _ in 0..100 {
+                game.import_state(None).unwrap();
+                while let Some(mov) = game.random_move(&mut rng) {
+                    let player = player_to_id(game.data.turn);
+                    game.is_legal_move(player, sync(&mov)).unwrap();
+                    game.make_move(player, sync(&mov)).unwrap();
+                }
+                assert!(game.data.result.is_over());
+            }
+        }
 
-        game.import_state(Some("#X")).unwrap();
-        let mut storage = vec![];
-        game.get_concrete_moves(1, &mut storage).unwrap();
-        assert_eq!(
-            [] as [move_code; 0],
-            MoveCode::slice_to_rust(&storage).as_ref()
-        );
+        let game = create_with_state("xxxxxx/oooooo/xxxxxx/oooooo/xxxxxx/oooooo/xxxxxx#o");
+        assert_eq!(None, game.random_move(&mut rng));
+        let game = create_with_state("x/x/x/x/ooo#O");
+        assert_eq!(None, game.random_move(&mut rng));
     }
 
     #[test]
-    fn is_legal_move() {
-        let mut game = create_with_state("/OXOOXO/#o");
-
-        let err = game.is_legal_move(1, sync(&0)).unwrap_err().code;
-        assert_eq!(InvalidInput, err);
+    fn replay() {
+        let options = GameOptions::new("7x6@4").unwrap();
+        let game = ConnectFour::replay(options, &[3, 3, 4, 2]).unwrap();
+        assert_eq!(create_with_state("//o/xo/x#x").id(), game.id());
+        assert_eq!(4, game.ply_count());
 
-        game.is_legal_move(2, sync(&0)).unwrap();
+        let game = ConnectFour::replay(options, &[]).unwrap();
+        assert_eq!(create_default(), game);
 
-        game.is_legal_move(2, sync(&1)).unwrap_err().code;
+        let full = [0; 7];
+        let err = ConnectFour::replay(options, &full).unwrap_err().code;
+        assert_eq!(InvalidInput, err);
+        let err = ConnectFour::replay(options, &[0, 7]).unwrap_err().code;
         assert_eq!(InvalidInput, err);
 
-        game.is_legal_move(2, sync(&DEFAULT_WIDTH.into()))
+        let win = [0, 1, 0, 1, 0, 1, 0];
+        let game = ConnectFour::replay(options, &win).unwrap();
+        assert_eq!(GameResult::Winner, game.data.result);
+        let err = ConnectFour::replay(options, &[0, 1, 0, 1, 0, 1, 0, 1])
             .unwrap_err()
             .code;
         assert_eq!(InvalidInput, err);
-
-        game.import_state(Some("#X")).unwrap();
-        game.is_legal_move(2, sync(&1)).unwrap_err().code;
-        assert_eq!(InvalidInput, err);
-
-        game.import_state(Some("#-")).unwrap();
-        game.is_legal_move(2, sync(&2)).unwrap_err().code;
-        assert_eq!(InvalidInput, err);
     }
 
     #[test]
-    fn make_move() {
-        let mut game = create_with_state("/OOO/#x");
+    fn three_players() {
+        let options = GameOptions::new("5x4@3p3").unwrap();
+        let mut game = ConnectFour::with_options(options);
+        assert_eq!(3, game.player_count().unwrap());
+
+        for (column, player) in [0, 1, 2, 0, 1, 2, 3, 4]
+            .into_iter()
+            .zip([1, 2, 3, 1, 2, 3, 1, 2])
+        {
+            let mut players = vec![];
+            game.players_to_move(&mut players).unwrap();
+            assert_eq!([player], *players);
+            game.is_legal_move(player, sync(&column)).unwrap();
+            game.make_move(player, sync(&column)).unwrap();
+        }
+        assert!(game.is_legal_move(1, sync(&2)).is_err());
+        game.make_move(3, sync(&2)).unwrap();
 
-        game.make_move(1, sync(&0)).unwrap();
-        game.make_move(2, sync(&1)).unwrap();
+        assert_eq!(GameResult::Winner, game.data.result);
+        let mut winners = vec![];
+        game.get_results(&mut winners).unwrap();
+        assert_eq!([3], *winners);
+        assert_eq!(Some(&[(2, 0), (2, 1), (2, 2)][..]), game.winning_line());
 
-        let expected = "X/OOOO/////#O";
         let mut storage = ValidCString::default();
         game.export_state(PLAYER_NONE, &mut storage).unwrap();
-        assert_eq!(expected, storage.as_ref());
-
-        game.import_state(Some("XXXOOO/OOOXXX/XXXOOO/OOOXXX/XXXOO/OOOXXX/XXXOOO#o"))
-            .unwrap();
-        game.make_move(2, sync(&4)).unwrap();
-        assert_eq!(GameResult::Draw, game.data.result);
+        assert_eq!("XX/OO/ZZZ/X/O#Z", storage.as_ref());
+        let mut copy = ConnectFour::with_options(options);
+        copy.import_state_strict(Some(storage.as_ref())).unwrap();
+        assert_eq!(game.id(), copy.id());
+
+        game.undo_move();
+        assert_eq!(2, game.data.turn);
+        let mut bytes = vec![];
+        game.export_serialized(&mut bytes);
+        let copy = ConnectFour::create(&GameInit::Serialized(&bytes)).unwrap();
+        assert_eq!(game.data.board, copy.data.board);
+        assert_eq!(2, copy.data.turn);
+
+        let err = copy
+            .clone()
+            .import_state_strict(Some("XX/OO/Z/X/O#z"))
+            .unwrap_err();
+        assert_eq!(InvalidInput, err.code);
+        assert!(copy.clone().import_state(Some("Y#x")).is_err());
+        assert!(create_default().import_state(Some("Z#x")).is_err());
+        assert!(create_default().import_state(Some("#z")).is_err());
     }
 
     #[test]
-    fn get_results() {
-        let mut game = create_with_state("/OXO/#x");
+    fn handicap() {
+        let mut game = ConnectFour::create(&GameInit::Standard {
+            opts: Some("5x4@4+h3"),
+            legacy: None,
+            state: None,
+        })
+        .unwrap();
+        for x in 0..5 {
+            let expected = if (1..=3).contains(&x) {
+                State::O
+            } else {
+                State::Empty
+            };
+            assert_eq!(expected, game[(x, 0)]);
+            assert_eq!(State::Empty, game[(x, 1)]);
+        }
+        assert_eq!(0, game.data.turn);
+        assert_eq!(GameResult::Ongoing, game.data.result);
+        assert_eq!(3, game.ply_count());
+        assert_eq!(None, game.undo_move());
 
-        let mut storage = vec![];
-        game.get_results(&mut storage).unwrap();
-        assert_eq!([] as [player_id; 0], *storage);
+        game.make_move(1, sync(&2)).unwrap();
+        assert_eq!(State::X, game[(2, 1)]);
+        game.import_state(None).unwrap();
+        assert_eq!(State::Empty, game[(2, 1)]);
+        assert_eq!(State::O, game[(2, 0)]);
 
-        game.import_state(Some("OOOO#O")).unwrap();
-        let mut storage = vec![];
-        game.get_results(&mut storage).unwrap();
-        assert_eq!([2], *storage);
+        let options = GameOptions::new("3x3@3+h2").unwrap();
+        let game = ConnectFour::replay(options, &[2]).unwrap();
+        assert_eq!(State::O, game[(0, 0)]);
+        assert_eq!(State::O, game[(1, 0)]);
+        assert_eq!(State::X, game[(2, 0)]);
 
-        game.import_state(Some("#-")).unwrap();
-        let mut storage = vec![];
-        game.get_results(&mut storage).unwrap();
-        assert_eq!([] as [player_id; 0], *storage);
+        for options in ["5x4@3+h3", "3x3@3+h7"] {
+            let err = ConnectFour::create(&GameInit::Standard {
+                opts: Some(options),
+                legacy: None,
+                state: None,
+            })
+            .unwrap_err();
+            assert_eq!(InvalidOptions, err.code);
+        }
     }
 
     #[test]
-    fn get_move_code() {
-        let mut game = create_default();
+    fn last_column() {
+        let mut game = create_with_state("X/O/X#o");
+        assert_eq!(None, game.last_column());
 
-        let mov = game.get_move_data(PLAYER_NONE, " 4 ").unwrap();
-        assert_eq!(4, mov);
+        game.import_state(Some("X/O/X#o@2")).unwrap();
+        assert_eq!(Some(2), game.last_column());
+        let mut storage = ValidCString::default();
+        game.export_state(PLAYER_NONE, &mut storage).unwrap();
+        assert_eq!("X/O/X////#o", storage.as_ref());
 
-        let err = game.get_move_data(PLAYER_NONE, "-3").unwrap_err().code;
-        assert_eq!(InvalidInput, err);
+        game.make_move(2, sync(&4)).unwrap();
+        assert_eq!(Some(4), game.last_column());
+        game.undo_move();
+        assert_eq!(Some(2), game.last_column());
+        game.import_state(Some(" X/O/X # o @ 1 ")).unwrap();
+        assert_eq!(Some(1), game.last_column());
+        assert_eq!(1, game.data.turn);
+
+        game.import_state(Some("X/O/X#X@0")).unwrap();
+        assert_eq!(GameResult::Winner, game.data.result);
+        assert_eq!(Some(0), game.last_column());
+
+        for state in [
+            "X/O/X#o@3",
+            "X/O/X#o@7",
+            "X/O/X#o@",
+            "X/O/X#o@a",
+            "X/O/X#@1",
+        ] {
+            assert!(game.import_state(Some(state)).is_err(), "{state}");
+        }
+
+        let mut game = create_pop_out("/X#o");
+        game.0.import_state(Some("/X#o@0")).unwrap();
+        assert_eq!(Some(0), game.0.last_column());
     }
 
     #[test]
@@ -1062,12 +5578,70 @@ mod tests {
         game.print(PLAYER_NONE, &mut storage).unwrap();
 
         assert_eq!(expected, storage.as_ref());
+        assert_eq!(expected, game.to_string());
+    }
+
+    #[test]
+    fn one_based() {
+        let mut game = ConnectFour::create(&GameInit::Standard {
+            opts: Some("10x2@2#"),
+            legacy: None,
+            state: Some("x/o////////#x"),
+        })
+        .unwrap();
+
+        assert_eq!(0, game.get_move_data(PLAYER_NONE, " 1 ").unwrap());
+        assert_eq!(9, game.get_move_data(PLAYER_NONE, "10").unwrap());
+        assert_eq!(2, game.get_move_data(PLAYER_NONE, "c").unwrap());
+        let err = game.get_move_data(PLAYER_NONE, "0").unwrap_err();
+        assert_eq!(InvalidInput, err.code);
+
+        let mut storage = ValidCString::default();
+        game.get_move_str(PLAYER_NONE, sync(&0), &mut storage)
+            .unwrap();
+        assert_eq!("1", storage.as_ref());
+
+        let expected = concat!(
+            "|  |  |  |  |  |  |  |  |  |  |\n",
+            "|XX|OO|  |  |  |  |  |  |  |  |\n",
+            "  1  2  3  4  5  6  7  8  9 10 \n",
+        );
+        assert_eq!(expected, game.to_string());
+
+        game.import_move_list("3 10").unwrap();
+        let mut storage = ValidCString::default();
+        game.export_move_list(&mut storage);
+        assert_eq!("3 10", storage.as_ref());
     }
 
     fn create_default() -> ConnectFour {
         ConnectFour::create(&GameInit::Default).unwrap()
     }
 
+    #[cfg(feature = "mirabel")]
+    #[test]
+    fn move_numbers() {
+        let mut game = create_with_state("X/O#x");
+        assert!(game.move_numbers().is_empty());
+
+        game.options.pop_out = true;
+        for code in [1, 0, 2, POP_FLAG | 1] {
+            let player = player_to_id(game.data.turn);
+            game.make_move(player, sync(&code)).unwrap();
+        }
+        assert_eq!(
+            vec![((0, 1), 4), ((1, 0), 3), ((2, 0), 5)],
+            game.move_numbers()
+        );
+        assert_eq!(6, game.ply_count());
+
+        game.undo_move();
+        assert_eq!(
+            vec![((0, 1), 4), ((1, 1), 3), ((2, 0), 5)],
+            game.move_numbers()
+        );
+    }
+
     fn create_with_state(string: &str) -> ConnectFour {
         ConnectFour::create(&GameInit::Standard {
             opts: None,
@@ -1077,6 +5651,15 @@ mod tests {
         .unwrap()
     }
 
+    fn create_pop_out(string: &str) -> PopOut {
+        PopOut::create(&GameInit::Standard {
+            opts: Some("4x4@3"),
+            legacy: None,
+            state: Some(string),
+        })
+        .unwrap()
+    }
+
     fn sync<M>(md: M) -> MoveDataSync<M> {
         MoveDataSync::with_default(md)
     }
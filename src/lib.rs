@@ -5,3 +5,7 @@ mod game;
 
 #[cfg(feature = "mirabel")]
 mod frontend;
+#[cfg(any(feature = "mirabel", feature = "export"))]
+mod style;
+#[cfg(feature = "export")]
+mod svg;
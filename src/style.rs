@@ -0,0 +1,22 @@
+//! Default colors and layout shared by the _mirabel_ frontend and the SVG
+//! export.
+
+/// Opaque color as red, green, and blue bytes.
+pub(crate) type Rgb = [u8; 3];
+
+/// Background color.
+pub(crate) const BACKGROUND: Rgb = [201, 144, 73];
+/// Frame color.
+pub(crate) const FRAME: Rgb = [161, 119, 67];
+/// Chip color for X.
+pub(crate) const CHIP_X: Rgb = [240, 217, 181];
+/// Chip color for O.
+pub(crate) const CHIP_O: Rgb = [199, 36, 73];
+/// Chip color for Z.
+pub(crate) const CHIP_Z: Rgb = [73, 138, 99];
+/// Chip color for Y.
+pub(crate) const CHIP_Y: Rgb = [58, 110, 165];
+/// Default width of a frame bar.
+pub(crate) const FRAME_WIDTH: f32 = 0.1;
+/// Minimum margin around the frame.
+pub(crate) const MARGIN: f32 = 0.1;
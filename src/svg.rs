@@ -0,0 +1,128 @@
+//! SVG export of the game board.
+//!
+//! Uses the same layout and default colors as the _mirabel_ frontend.
+
+use std::fmt::Write;
+
+use crate::{
+    game::{ConnectFour, State},
+    style::{self, Rgb, FRAME_WIDTH, MARGIN},
+};
+
+/// Format an opaque `rgb` color as hex code.
+fn hex([r, g, b]: Rgb) -> String {
+    format!("#{r:02x}{g:02x}{b:02x}")
+}
+
+impl ConnectFour {
+    /// Render the board as an SVG image.
+    ///
+    /// Each cell is 1x1 and the center of the top-left cell is the origin.
+    pub(crate) fn export_svg(&self) -> String {
+        const ERROR: &str = "writing SVG failed";
+
+        let (width, height) = (self.options().width(), self.options().height());
+        let (w, h) = (f32::from(width), f32::from(height));
+        let min = -0.5 - 0.5 * FRAME_WIDTH - MARGIN;
+        let (svg_w, svg_h) = (w + FRAME_WIDTH + 2. * MARGIN, h + FRAME_WIDTH + 2. * MARGIN);
+        let frame = hex(style::FRAME);
+        let mut svg = String::new();
+
+        writeln!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="{min} {min} {svg_w} {svg_h}">"#
+        )
+        .expect(ERROR);
+        writeln!(
+            svg,
+            r#"<rect x="{min}" y="{min}" width="{svg_w}" height="{svg_h}" fill="{}"/>"#,
+            hex(style::BACKGROUND),
+        )
+        .expect(ERROR);
+
+        // SVG coordinates grow downwards.
+        for y in 0..height {
+            for x in 0..width {
+                let (cx, cy) = (f32::from(x), h - 1. - f32::from(y));
+                let fill = hex(match self[(x, y)] {
+                    State::Empty => continue,
+                    State::X => style::CHIP_X,
+                    State::O => style::CHIP_O,
+                    State::Z => style::CHIP_Z,
+                    State::Y => style::CHIP_Y,
+                    State::Blocked => {
+                        writeln!(
+                            svg,
+                            r#"<rect x="{}" y="{}" width="1" height="1" fill="{frame}"/>"#,
+                            cx - 0.5,
+                            cy - 0.5,
+                        )
+                        .expect(ERROR);
+                        continue;
+                    }
+                });
+                writeln!(
+                    svg,
+                    r#"<circle cx="{cx}" cy="{cy}" r="0.5" fill="{fill}"/>"#
+                )
+                .expect(ERROR);
+            }
+        }
+
+        let start = -0.5 - 0.5 * FRAME_WIDTH;
+        for x in 0..=width {
+            writeln!(
+                svg,
+                r#"<rect x="{}" y="{start}" width="{FRAME_WIDTH}" height="{}" fill="{frame}"/>"#,
+                start + f32::from(x),
+                h + FRAME_WIDTH,
+            )
+            .expect(ERROR);
+        }
+        for y in 0..=height {
+            writeln!(
+                svg,
+                r#"<rect x="{start}" y="{}" width="{}" height="{FRAME_WIDTH}" fill="{frame}"/>"#,
+                start + f32::from(y),
+                w + FRAME_WIDTH,
+            )
+            .expect(ERROR);
+        }
+        svg.push_str("</svg>\n");
+
+        svg
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mirabel::{game::GameMethods, game_init::GameInit};
+
+    use super::*;
+
+    #[test]
+    fn hex_colors() {
+        assert_eq!("#c99049", hex(style::BACKGROUND));
+        assert_eq!("#000000", hex([0, 0, 0]));
+        assert_eq!("#ff0a01", hex([255, 10, 1]));
+    }
+
+    #[test]
+    fn export_svg() {
+        let game = ConnectFour::create(&GameInit::Standard {
+            opts: Some("5x4@3"),
+            legacy: None,
+            state: Some("xo/*x//o#x"),
+        })
+        .unwrap();
+
+        let svg = game.export_svg();
+        assert!(svg.starts_with("<svg "));
+        assert!(svg.ends_with("</svg>\n"));
+        assert_eq!(4, svg.matches("<circle").count());
+        assert_eq!(2, svg.matches(&hex(style::CHIP_X)).count());
+        assert_eq!(2, svg.matches(&hex(style::CHIP_O)).count());
+        // Background, blocked cell, and frame bars.
+        assert_eq!(1 + 1 + 6 + 5, svg.matches("<rect").count());
+    }
+}